@@ -0,0 +1,89 @@
+#!/bin/bash
+
+# 生成 src/tester/error_codes.rs（--error 使用的错误名称 -> 错误码表）
+#
+# 用法:
+#   scripts/gen_error_codes.sh <mysqld_error.h | messages_to_clients.txt> <errmsg.h> > src/tester/error_codes.rs
+#
+# 服务端错误可取自编译产物 include/mysqld_error.h，或 MySQL 源码中的
+# share/messages_to_clients.txt（comp_err 的输入，按 start-error-number 顺序编号）。
+# 客户端错误取自 include/errmsg.h。OBSOLETE_ 前缀的错误以原名保留，
+# 以便针对旧版本服务端的测试仍能使用这些名称。
+
+set -euo pipefail
+
+if [ $# -ne 2 ]; then
+    echo "usage: $0 <mysqld_error.h|messages_to_clients.txt> <errmsg.h>" >&2
+    exit 1
+fi
+SERVER_SOURCE=$1
+CLIENT_HEADER=$2
+
+# 输出 "NAME CODE"，按错误码排序；同名时保留未废弃的条目
+server_errors() {
+    if grep -qE '^#define (ER|WARN)_' "$SERVER_SOURCE"; then
+        awk '$1 == "#define" && $2 ~ /^(OBSOLETE_)?(ER|WARN)_[A-Z0-9_]+$/ && $3 ~ /^[0-9]+$/ { print $2, $3 }' "$SERVER_SOURCE"
+    else
+        awk '
+            $1 == "start-error-number" { code = $2; next }
+            /^(OBSOLETE_)?(ER|WARN)_[A-Z0-9_]+([ \t]|$)/ { print $1, code; code++ }
+        ' "$SERVER_SOURCE"
+    fi | awk '
+        {
+            name = $1; obsolete = sub(/^OBSOLETE_/, "", name)
+            if (!(name in code) || (seen_obsolete[name] && !obsolete)) {
+                if (!(name in code)) order[++n] = name
+                code[name] = $2; seen_obsolete[name] = obsolete
+            }
+        }
+        END { for (i = 1; i <= n; i++) print order[i], code[order[i]] }
+    ' | sort -k2,2n -s
+}
+
+client_errors() {
+    awk '$1 == "#define" && $2 ~ /^CR_[A-Z0-9_]+$/ && $3 ~ /^[0-9]+$/ &&
+         $2 !~ /^CR_(MIN|MAX)_ERROR$|^CR_ERROR_(FIRST|LAST)$/ { print $2, $3 }' "$CLIENT_HEADER" |
+        sort -k2,2n -s
+}
+
+SERVER=$(server_errors)
+CLIENT=$(client_errors)
+if [ -z "$SERVER" ] || [ -z "$CLIENT" ]; then
+    echo "no error definitions found" >&2
+    exit 1
+fi
+
+cat <<'RUST'
+//! MySQL error name to error code table used by `--error`.
+//!
+//! Generated by `scripts/gen_error_codes.sh` from the server error list
+//! (`mysqld_error.h` or `share/messages_to_clients.txt`) and the client
+//! errors in `errmsg.h`. Do not edit by hand; rerun the script instead.
+
+use phf::phf_map;
+
+/// Server (`ER_*`, `WARN_*`) and client (`CR_*`) error names
+pub static ERROR_CODES: phf::Map<&'static str, u16> = phf_map! {
+    // Server errors
+RUST
+echo "$SERVER" | awk '{ printf "    \"%s\" => %s,\n", $1, $2 }'
+echo "    // Client errors"
+echo "$CLIENT" | awk '{ printf "    \"%s\" => %s,\n", $1, $2 }'
+cat <<'RUST'
+};
+
+/// Look up the code for an error name such as `ER_NO_SUCH_TABLE`
+pub fn error_code(name: &str) -> Option<u16> {
+    ERROR_CODES.get(name).copied()
+}
+
+/// Reverse lookup of [`error_code`]. A few codes have several names; the
+/// `ER_*` one is preferred, then the alphabetically first.
+pub fn error_name(code: u16) -> Option<&'static str> {
+    ERROR_CODES
+        .entries()
+        .filter(|(_, &c)| c == code)
+        .map(|(name, _)| *name)
+        .min_by_key(|name| (!name.starts_with("ER_"), *name))
+}
+RUST
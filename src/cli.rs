@@ -2,9 +2,12 @@
 //!
 //! This module defines all CLI arguments compatible with the Go version.
 
+use crate::tester::watchdog::parse_duration;
+use crate::util::charset::{parse_charset, Charset};
 use anyhow::{anyhow, Result};
 use clap::Parser;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use walkdir::WalkDir;
 
 /// MySQL Test Runner (Rust) - A MySQL testing framework
@@ -129,6 +132,72 @@ pub struct Args {
     /// Maximum number of database connections in the pool
     #[arg(long, default_value = "0")]
     pub max_connections: usize,
+
+    /// Omit column name headers in query results, for result files recorded
+    /// before headers were introduced
+    #[arg(long)]
+    pub legacy_result_format: bool,
+
+    /// Maximum iterations of a single while loop before the test fails
+    /// (a test can override it with --max_loop_iterations)
+    #[arg(long, default_value = "10000")]
+    pub max_loop_iterations: usize,
+
+    /// Scratch directory exposed to tests as $MYSQLTEST_VARDIR, relative to the
+    /// test root (the working directory holding t/ and r/); created once at startup
+    #[arg(long, default_value = "var")]
+    pub vardir: String,
+
+    /// mysql client binary exposed to tests as $MYSQL
+    #[arg(long, default_value = "mysql")]
+    pub mysql_client: String,
+
+    /// Send statements through prepare/execute (binary protocol) where possible
+    #[arg(long)]
+    pub ps_protocol: bool,
+
+    /// Run each SELECT through a temporary view (mysqltest_tmp_v)
+    #[arg(long, conflicts_with = "sp_protocol")]
+    pub view_protocol: bool,
+
+    /// Run each SELECT through a temporary stored procedure (mysqltest_tmp_sp)
+    #[arg(long)]
+    pub sp_protocol: bool,
+
+    /// Kill a statement running longer than this (e.g. 30s, 500ms, 2m) and fail the test
+    #[arg(long, value_parser = parse_duration)]
+    pub query_timeout: Option<Duration>,
+
+    /// Fail a test file that runs longer than this; the running statement is killed
+    #[arg(long, value_parser = parse_duration)]
+    pub test_timeout: Option<Duration>,
+
+    /// Maximum lines of the result diff shown in reports (0 = unlimited);
+    /// the .reject file always has the full output
+    #[arg(long, default_value = "200")]
+    pub max_diff_lines: usize,
+
+    /// Keep comparing after a result mismatch (resynchronising at the next
+    /// statement) and report every mismatch of a test
+    #[arg(long)]
+    pub continue_on_mismatch: bool,
+
+    /// Review result changes hunk by hunk (accept / reject / accept all) and
+    /// write back only the accepted ones
+    #[arg(long, conflicts_with_all = ["record", "record_pending"])]
+    pub review: bool,
+
+    /// Write changed output to <result>.new next to the result file for later review
+    #[arg(long, conflicts_with = "record")]
+    pub record_pending: bool,
+
+    /// Connection character set (e.g. gbk, latin1); results are decoded with it
+    #[arg(long, value_parser = parse_charset)]
+    pub character_set: Option<Charset>,
+
+    /// Encoding of .test/.result files without a '# charset: <name>' first line (default UTF-8)
+    #[arg(long, value_parser = parse_charset)]
+    pub file_charset: Option<Charset>,
 }
 
 /// Represents a resolved test input
@@ -178,6 +247,14 @@ impl Args {
             return Err(anyhow!("Retry connection count must be at least 1"));
         }
 
+        if self.review && self.parallel > 1 {
+            return Err(anyhow!("--review asks questions per test and cannot run with --parallel"));
+        }
+
+        if self.max_loop_iterations == 0 {
+            return Err(anyhow!("Max loop iterations must be at least 1"));
+        }
+
         // Validate test files when not using --all
         if !self.all && self.test_files.is_empty() {
             return Err(anyhow!(
@@ -191,6 +268,17 @@ impl Args {
         Ok(())
     }
 
+    /// Create the --vardir directory once for the whole run and store its
+    /// absolute path, so every test sees the same $MYSQLTEST_VARDIR
+    pub fn prepare_vardir(&mut self) -> Result<()> {
+        std::fs::create_dir_all(&self.vardir)
+            .map_err(|e| anyhow!("Failed to create vardir '{}': {}", self.vardir, e))?;
+        let vardir = std::fs::canonicalize(&self.vardir)
+            .map_err(|e| anyhow!("Failed to resolve vardir '{}': {}", self.vardir, e))?;
+        self.vardir = vardir.to_string_lossy().into_owned();
+        Ok(())
+    }
+
     /// Resolve test inputs to actual test files
     /// Supports:
     /// - Directory paths: runs all .test files in the directory
@@ -507,6 +595,21 @@ mod tests {
             allure_dir: "".to_string(),
             parallel: 1,
             max_connections: 0,
+            legacy_result_format: false,
+            max_loop_iterations: 10_000,
+            vardir: "var".to_string(),
+            mysql_client: "mysql".to_string(),
+            ps_protocol: false,
+            view_protocol: false,
+            sp_protocol: false,
+            query_timeout: None,
+            test_timeout: None,
+            max_diff_lines: 200,
+            continue_on_mismatch: false,
+            review: false,
+            record_pending: false,
+            character_set: None,
+            file_charset: None,
         }
     }
 
@@ -536,6 +639,20 @@ mod tests {
         std::env::set_current_dir(original_dir).unwrap();
     }
 
+    #[test]
+    fn test_prepare_vardir() {
+        let temp_dir = tempdir().unwrap();
+        let mut args = create_test_args(vec![]);
+        args.vardir = temp_dir.path().join("run").join("var").display().to_string();
+        args.prepare_vardir().unwrap();
+
+        assert!(std::path::Path::new(&args.vardir).is_dir());
+        assert_eq!(
+            std::path::PathBuf::from(&args.vardir),
+            fs::canonicalize(temp_dir.path().join("run/var")).unwrap()
+        );
+    }
+
     #[test]
     fn test_resolve_test_with_extension() {
         // Create a temporary directory structure
@@ -22,10 +22,10 @@ use stub::email::MailSender;
 
 fn main() -> Result<()> {
     // Parse command line arguments
-    let args = Args::parse_args();
+    let mut args = Args::parse_args();
 
     // Validate arguments
-    if let Err(e) = args.validate() {
+    if let Err(e) = args.validate().and_then(|_| args.prepare_vardir()) {
         eprintln!("Error: {}", e);
         std::process::exit(1);
     }
@@ -125,6 +125,14 @@ impl AllureRenderer {
                         Some(case.errors.join("\n"))
                     },
                 })
+            } else if case.status == TestStatus::Skipped {
+                Some(AllureStatusDetails {
+                    known: false,
+                    muted: false,
+                    flaky: false,
+                    message: case.skip_reason.clone(),
+                    trace: None,
+                })
             } else {
                 None
             },
@@ -217,6 +225,14 @@ impl AllureRenderer {
                         case.errors.first().unwrap_or(&"No error details".to_string())
                     )),
                 })
+            } else if case.status == TestStatus::Skipped {
+                Some(AllureStatusDetails {
+                    known: false,
+                    muted: false,
+                    flaky: false,
+                    message: case.skip_reason.clone(),
+                    trace: None,
+                })
             } else {
                 Some(AllureStatusDetails {
                     known: false,
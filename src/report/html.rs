@@ -82,6 +82,10 @@ pub fn generate_plain_text_report(suite_result: &TestSuiteResult) -> String {
                 case.duration_ms
             ));
 
+            if let Some(reason) = &case.skip_reason {
+                report.push_str(&format!("     跳过原因: {}\n", reason));
+            }
+
             // 显示错误信息（如果有）
             if !case.errors.is_empty() {
                 for error in &case.errors {
@@ -338,11 +338,20 @@ impl ReportRenderer for SimpleHtmlRenderer {
                 html.push_str(&format!("        <td>{}</td>\n", case.passed_queries));
                 html.push_str(&format!("        <td>{}</td>\n", case.failed_queries));
 
-                let first_error = case
-                    .errors
-                    .first()
-                    .map(|e| html_escape(e))
-                    .unwrap_or_else(|| "-".to_string());
+                let mut first_error = match &case.skip_reason {
+                    Some(reason) => format!("跳过原因: {}", html_escape(reason)),
+                    None => case
+                        .errors
+                        .first()
+                        .map(|e| html_escape(e))
+                        .unwrap_or_else(|| "-".to_string()),
+                };
+                if let Some(diff) = &case.diff {
+                    first_error.push_str(&format!(
+                        "<details><summary>diff</summary><pre>{}</pre></details>",
+                        html_escape(diff)
+                    ));
+                }
                 html.push_str(&format!("        <td>{}</td>\n", first_error));
                 html.push_str("      </tr>\n");
             }
@@ -470,7 +479,11 @@ impl ReportRenderer for XunitRenderer {
                     xml.push_str(r#"    <failure message="Test failed" type="TestFailure">"#);
                     xml.push('\n');
                     if !case.errors.is_empty() {
-                        let error_text = case.errors.join("\n");
+                        let mut error_text = case.errors.join("\n");
+                        if let Some(diff) = &case.diff {
+                            error_text.push_str("\n\n");
+                            error_text.push_str(diff);
+                        }
                         xml.push_str(&format!("<![CDATA[{}]]>", error_text));
                         xml.push('\n');
                     }
@@ -500,7 +513,13 @@ impl ReportRenderer for XunitRenderer {
                         case.duration_ms as f64 / 1000.0
                     ));
                     xml.push('\n');
-                    xml.push_str("    <skipped/>\n");
+                    match &case.skip_reason {
+                        Some(reason) => xml.push_str(&format!(
+                            "    <skipped message=\"{}\"/>\n",
+                            xunit::escape_xml(reason)
+                        )),
+                        None => xml.push_str("    <skipped/>\n"),
+                    }
                     xml.push_str("  </testcase>\n");
                 }
             }
@@ -549,6 +568,20 @@ impl ReportRenderer for TerminalRenderer {
             output.push_str(&format!("Pass rate: {:.1}%\n", suite.pass_rate()));
         }
 
+        // 跳过详情
+        if suite.skipped_tests() > 0 {
+            output.push_str("Skipped tests:\n");
+            for case in &suite.cases {
+                if case.status == TestStatus::Skipped {
+                    output.push_str(&format!(
+                        "  • {}: {}\n",
+                        case.test_name,
+                        case.skip_reason.as_deref().unwrap_or("no reason given")
+                    ));
+                }
+            }
+        }
+
         // 失败详情
         if suite.failed_tests() > 0 {
             output.push_str("Failed tests:\n");
@@ -558,6 +591,11 @@ impl ReportRenderer for TerminalRenderer {
                     for error in &case.errors {
                         output.push_str(&format!("    {}\n", error));
                     }
+                    if let Some(diff) = &case.diff {
+                        for line in diff.lines() {
+                            output.push_str(&format!("    {}\n", line));
+                        }
+                    }
                 }
             }
         }
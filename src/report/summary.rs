@@ -40,12 +40,34 @@ pub fn print_case_result(case: &TestResult) {
                 "{} {} {}",
                 style("⚠").yellow(),
                 case.test_name,
-                style("(skipped)").yellow()
+                style(match &case.skip_reason {
+                    Some(reason) => format!("(skipped: {})", reason),
+                    None => "(skipped)".to_string(),
+                })
+                .yellow()
             );
         }
     }
 }
 
+/// Print a unified diff, colored by line kind
+fn print_diff(diff: &str) {
+    for line in diff.lines() {
+        let styled = if line.starts_with("+++") || line.starts_with("---") {
+            style(line).bold()
+        } else if line.starts_with('+') {
+            style(line).green()
+        } else if line.starts_with('-') {
+            style(line).red()
+        } else if line.starts_with("@@") {
+            style(line).cyan()
+        } else {
+            style(line)
+        };
+        println!("    {}", styled);
+    }
+}
+
 /// Print running test indicator
 pub fn print_running_test(test_name: &str) {
     print!("{} running {} ... ", style("▶").blue(), test_name);
@@ -110,6 +132,9 @@ pub fn print_summary(suite: &TestSuiteResult) {
                 for error in &case.errors {
                     println!("    {}", style(error).red());
                 }
+                if let Some(diff) = &case.diff {
+                    print_diff(diff);
+                }
             }
         }
         println!();
@@ -129,7 +154,12 @@ mod tests {
         } else {
             vec![]
         };
-        
+        let skip_reason = if status == TestStatus::Skipped {
+            Some("Test skipped".to_string())
+        } else {
+            None
+        };
+
         TestResult {
             test_name: name.to_string(),
             success,
@@ -144,6 +174,8 @@ mod tests {
             stderr: "".to_string(),
             classname: format!("test.{}", name),
             query_failures: vec![],
+            skip_reason,
+            diff: None,
         }
     }
 
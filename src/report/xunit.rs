@@ -107,7 +107,11 @@ fn write_test_case(file: &mut File, case: &crate::tester::tester::TestResult) ->
                 r#"    <failure message="Test failed" type="TestFailure">"#
             )?;
             if !case.errors.is_empty() {
-                let error_text = case.errors.join("\n");
+                let mut error_text = case.errors.join("\n");
+                if let Some(diff) = &case.diff {
+                    error_text.push_str("\n\n");
+                    error_text.push_str(diff);
+                }
                 writeln!(file, "<![CDATA[{}]]>", error_text)?;
             }
             writeln!(file, "    </failure>")?;
@@ -138,7 +142,14 @@ fn write_test_case(file: &mut File, case: &crate::tester::tester::TestResult) ->
                 escape_xml(&case.classname),
                 case.duration_ms as f64 / 1000.0
             )?;
-            writeln!(file, "    <skipped/>")?;
+            match &case.skip_reason {
+                Some(reason) => writeln!(
+                    file,
+                    r#"    <skipped message="{}"/>"#,
+                    escape_xml(reason)
+                )?,
+                None => writeln!(file, "    <skipped/>")?,
+            }
             writeln!(file, "  </testcase>")?;
         }
     }
@@ -177,6 +188,7 @@ mod tests {
         failed_test.mark_failed();
         failed_test.add_error("Assertion failed".to_string());
         failed_test.set_stdout("Some output".to_string());
+        failed_test.diff = Some("--- r/t.result\n+++ r/t.reject\n@@ -1 +1 @@\n-1\n+2\n".to_string());
         suite.add_case(failed_test);
 
         // Write to temporary file
@@ -192,6 +204,7 @@ mod tests {
         assert!(content.contains("test_failed"));
         assert!(content.contains("failure"));
         assert!(content.contains("Assertion failed"));
+        assert!(content.contains("@@ -1 +1 @@\n-1\n+2"));
     }
 
     #[test]
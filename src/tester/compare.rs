@@ -0,0 +1,216 @@
+//! Tolerant result comparison (--compare / --enable_compare)
+//!
+//! By default every output line must equal the result file line (apart from
+//! result file markers). With comparison options, lines are split into tab
+//! separated cells and compared cell by cell:
+//!
+//! - `float_epsilon=<e>`: numeric cells are compared numerically (`1.50`
+//!   equals `1.5`) and may differ by `e`, relative to the larger magnitude
+//!   once it exceeds 1
+//! - `ignore_trailing_ws`: trailing spaces of a cell are ignored
+//! - `case_insensitive`: letter case is ignored
+//! - `json`: cells holding JSON objects or arrays are compared structurally,
+//!   ignoring key order and whitespace (implied by `--json_result`)
+//! - `exact`: back to exact comparison
+
+use crate::tester::handlers::json_result;
+use crate::tester::result_pattern;
+use anyhow::{anyhow, Result};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CompareOptions {
+    pub float_epsilon: Option<f64>,
+    pub ignore_trailing_ws: bool,
+    pub case_insensitive: bool,
+    pub json: bool,
+}
+
+impl CompareOptions {
+    /// Parse `float_epsilon=1e-9 ignore_trailing_ws case_insensitive`
+    /// (space or comma separated)
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut options = CompareOptions::default();
+        for option in spec
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|s| !s.is_empty())
+        {
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (option, None),
+            };
+            match (name.to_ascii_lowercase().as_str(), value) {
+                ("float_epsilon", Some(value)) => {
+                    let epsilon: f64 = value
+                        .parse()
+                        .map_err(|_| anyhow!("Invalid float_epsilon '{}'", value))?;
+                    if !epsilon.is_finite() || epsilon < 0.0 {
+                        return Err(anyhow!("float_epsilon must be a non-negative number, got '{}'", value));
+                    }
+                    options.float_epsilon = Some(epsilon);
+                }
+                ("ignore_trailing_ws", None) => options.ignore_trailing_ws = true,
+                ("case_insensitive", None) => options.case_insensitive = true,
+                ("json", None) => options.json = true,
+                ("exact", None) => options = CompareOptions::default(),
+                _ => {
+                    return Err(anyhow!(
+                        "Unknown compare option '{}' (expected float_epsilon=<e>, ignore_trailing_ws, case_insensitive, json or exact)",
+                        option
+                    ))
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    /// No option set: lines are compared exactly
+    pub fn is_exact(&self) -> bool {
+        *self == CompareOptions::default()
+    }
+
+    /// Compare an expected result line with an actual output line. The error
+    /// names the first cell that differs and why.
+    pub fn compare_line(&self, expected: &str, actual: &str) -> std::result::Result<(), String> {
+        if result_pattern::line_matches(expected, actual) {
+            return Ok(());
+        }
+        if self.is_exact() {
+            return Err("line differs".to_string());
+        }
+
+        let expected_cells: Vec<&str> = expected.split('\t').collect();
+        let actual_cells: Vec<&str> = actual.split('\t').collect();
+        if expected_cells.len() != actual_cells.len() {
+            return Err(format!(
+                "expected {} cells, got {}",
+                expected_cells.len(),
+                actual_cells.len()
+            ));
+        }
+        for (index, (e, a)) in expected_cells.iter().zip(&actual_cells).enumerate() {
+            self.compare_cell(e, a)
+                .map_err(|reason| format!("cell {}: {}", index + 1, reason))?;
+        }
+        Ok(())
+    }
+
+    fn compare_cell(&self, expected: &str, actual: &str) -> std::result::Result<(), String> {
+        if result_pattern::line_matches(expected, actual) {
+            return Ok(());
+        }
+        let (expected, actual) = if self.ignore_trailing_ws {
+            (expected.trim_end(), actual.trim_end())
+        } else {
+            (expected, actual)
+        };
+
+        if self.json {
+            if let (Some(e), Some(a)) = (json_result::parse(expected), json_result::parse(actual)) {
+                if e.is_object() || e.is_array() || a.is_object() || a.is_array() {
+                    return if e == a {
+                        Ok(())
+                    } else {
+                        Err(format!("JSON '{}' vs '{}'", expected, actual))
+                    };
+                }
+            }
+        }
+
+        if let (Some(epsilon), Ok(e), Ok(a)) = (
+            self.float_epsilon,
+            expected.parse::<f64>(),
+            actual.parse::<f64>(),
+        ) {
+            let tolerance = epsilon * e.abs().max(a.abs()).max(1.0);
+            let difference = (e - a).abs();
+            if difference <= tolerance {
+                return Ok(());
+            }
+            return Err(format!(
+                "'{}' vs '{}' differ by {:e}, more than float_epsilon={:e}",
+                expected, actual, difference, epsilon
+            ));
+        }
+
+        let equal = if self.case_insensitive {
+            expected.to_lowercase() == actual.to_lowercase()
+        } else {
+            expected == actual
+        };
+        if equal {
+            Ok(())
+        } else {
+            Err(format!("'{}' vs '{}'", expected, actual))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let options = CompareOptions::parse("float_epsilon=1e-9 ignore_trailing_ws, case_insensitive").unwrap();
+        assert_eq!(options.float_epsilon, Some(1e-9));
+        assert!(options.ignore_trailing_ws);
+        assert!(options.case_insensitive);
+        assert!(CompareOptions::parse("float_epsilon=1e-9 exact").unwrap().is_exact());
+        assert!(CompareOptions::parse("").unwrap().is_exact());
+        assert!(CompareOptions::parse("float_epsilon=abc").is_err());
+        assert!(CompareOptions::parse("float_epsilon=-1").is_err());
+        assert!(CompareOptions::parse("fuzzy").is_err());
+        assert!(CompareOptions::parse("json").unwrap().json);
+    }
+
+    #[test]
+    fn test_exact_comparison() {
+        let exact = CompareOptions::default();
+        assert!(exact.compare_line("a\t1", "a\t1").is_ok());
+        assert!(exact.compare_line("a\t1.0", "a\t1").is_err());
+        // Result file markers work in every mode
+        assert!(exact.compare_line("a\t{{INT}}", "a\t7").is_ok());
+    }
+
+    #[test]
+    fn test_float_epsilon() {
+        let options = CompareOptions::parse("float_epsilon=1e-9").unwrap();
+        assert!(options.compare_line("avg\t0.3333333333", "avg\t0.3333333334").is_ok());
+        assert!(options.compare_line("1.50", "1.5").is_ok());
+        // Relative to the magnitude of large values
+        assert!(options.compare_line("12345678901.5", "12345678901.50001").is_ok());
+
+        let error = options.compare_line("avg\t0.33", "avg\t0.34").unwrap_err();
+        assert!(error.starts_with("cell 2: '0.33' vs '0.34' differ by"), "{}", error);
+        assert_eq!(
+            options.compare_line("a\tb", "a\tb\tc").unwrap_err(),
+            "expected 2 cells, got 3"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let options = CompareOptions::parse("json").unwrap();
+        assert!(options.compare_line("1\t{\"a\":1,\"b\":[1,2]}", "1\t{\"b\": [1, 2], \"a\": 1}").is_ok());
+        assert_eq!(
+            options.compare_line("[1,2]", "[2,1]").unwrap_err(),
+            "cell 1: JSON '[1,2]' vs '[2,1]'"
+        );
+        // Numbers inside JSON are not rounded through f64
+        assert!(options.compare_line("{\"n\":12345678901234567890123}", "{\"n\":12345678901234567890124}").is_err());
+        assert!(options.compare_line("[0.123456789012345678901234]", "[0.123456789012345678901235]").is_err());
+        // Scalars are still compared as text
+        assert!(options.compare_line("1.0", "1").is_err());
+    }
+
+    #[test]
+    fn test_whitespace_and_case() {
+        let options = CompareOptions::parse("ignore_trailing_ws case_insensitive").unwrap();
+        assert!(options.compare_line("abc  \tX", "ABC\tx ").is_ok());
+        assert!(options.compare_line(" abc", "abc").is_err());
+        assert_eq!(
+            CompareOptions::parse("ignore_trailing_ws").unwrap().compare_line("a\tb", "a\tB").unwrap_err(),
+            "cell 2: 'b' vs 'B'"
+        );
+    }
+}
@@ -3,11 +3,28 @@
 //! This module manages multiple database connections for test execution,
 //! allowing tests to create, switch between, and manage multiple database connections.
 
-use super::database::{create_database_with_retry, ConnectionInfo, Database};
+use super::database::{create_database_with_retry, ConnectionInfo, Database, QueryResult};
+use super::value_format::ValueFormat;
+use super::watchdog::{HangDiagnostics, Watchdog};
 use anyhow::{anyhow, Result};
 use log::{debug, info};
 use mysql::PooledConn;
 use std::collections::HashMap;
+use std::thread::JoinHandle;
+
+/// A statement started by --send. The connection's `Database` is moved into the
+/// worker thread so the statement runs in the same session, and is handed back on reap.
+#[derive(Debug)]
+struct PendingStatement {
+    sql: String,
+    handle: JoinHandle<(Database, Result<QueryResult>)>,
+    /// Kills the statement if it runs past its timeout
+    watchdog: Option<Watchdog>,
+}
+
+/// A reaped --send statement: its SQL, its outcome and the watchdog's
+/// diagnostics if it timed out
+pub type Reaped = (String, Result<QueryResult>, Option<HangDiagnostics>);
 
 /// Connection manager for handling multiple database connections
 #[derive(Debug)]
@@ -20,6 +37,10 @@ pub struct ConnectionManager {
     default_connection_info: ConnectionInfo,
     /// Maximum retry count for connections
     max_retries: u32,
+    /// Statements started with --send, keyed by connection name
+    pending: HashMap<String, PendingStatement>,
+    /// Result value rendering of every connection (--value_format)
+    value_format: ValueFormat,
 }
 
 const DEFAULT_CONNECTION_NAME: &str = "default";
@@ -40,13 +61,110 @@ impl ConnectionManager {
             current_connection: DEFAULT_CONNECTION_NAME.to_string(),
             default_connection_info,
             max_retries,
+            pending: HashMap::new(),
+            value_format: ValueFormat::default(),
         })
     }
 
+    fn pending_error(&self, conn_name: &str) -> anyhow::Error {
+        anyhow!(
+            "Connection '{}' has a pending statement started by --send: {}; use --reap first",
+            conn_name,
+            self.pending
+                .get(conn_name)
+                .map(|p| p.sql.as_str())
+                .unwrap_or_default()
+        )
+    }
+
+    /// Name of the current active connection
+    pub fn current_connection_name(&self) -> &str {
+        &self.current_connection
+    }
+
+    /// Whether a --send statement is still running (or unreaped) on the connection
+    pub fn has_pending(&self, conn_name: &str) -> bool {
+        self.pending.contains_key(conn_name)
+    }
+
+    /// Names of all connections with an unreaped --send statement
+    pub fn pending_connections(&self) -> Vec<String> {
+        self.pending.keys().cloned().collect()
+    }
+
+    /// Start `sql` on the current connection in a background thread (--send),
+    /// through prepare/execute when `ps_protocol` is set. `watchdog` watches the
+    /// statement until it is reaped.
+    pub fn send(&mut self, sql: &str, ps_protocol: bool, watchdog: Option<Watchdog>) -> Result<()> {
+        let conn_name = self.current_connection.clone();
+        if self.pending.contains_key(&conn_name) {
+            return Err(anyhow!(
+                "Cannot --send on connection '{}': previous statement has not been reaped ({})",
+                conn_name,
+                self.pending[&conn_name].sql
+            ));
+        }
+
+        let mut database = self
+            .connections
+            .remove(&conn_name)
+            .ok_or_else(|| anyhow!("Current connection '{}' not found", conn_name))?;
+
+        let thread_sql = sql.to_string();
+        let handle = std::thread::Builder::new()
+            .name(format!("send-{}", conn_name))
+            .spawn(move || {
+                let result = if ps_protocol {
+                    database.query_prepared(&thread_sql)
+                } else {
+                    database.query_with_metadata(&thread_sql)
+                };
+                (database, result)
+            })?;
+
+        debug!("Sent statement on connection '{}': {}", conn_name, sql);
+        self.pending.insert(
+            conn_name,
+            PendingStatement {
+                sql: sql.to_string(),
+                handle,
+                watchdog,
+            },
+        );
+        Ok(())
+    }
+
+    /// Wait for the --send statement on `conn_name` and return its SQL and result (--reap)
+    pub fn reap(&mut self, conn_name: &str) -> Result<Reaped> {
+        let pending = self.pending.remove(conn_name).ok_or_else(|| {
+            anyhow!(
+                "No pending statement to --reap on connection '{}'",
+                conn_name
+            )
+        })?;
+
+        let (mut database, result) = pending.handle.join().map_err(|_| {
+            anyhow!(
+                "Worker thread for connection '{}' panicked while running: {}",
+                conn_name,
+                pending.sql
+            )
+        })?;
+        let hang = pending.watchdog.and_then(Watchdog::disarm);
+
+        database.set_value_format(self.value_format.clone());
+        self.connections.insert(conn_name.to_string(), database);
+        debug!("Reaped statement on connection '{}'", conn_name);
+        Ok((pending.sql, result, hang))
+    }
+
     /// Get a connection from the pool for concurrent execution.
     /// This returns a raw `PooledConn` which can be used in a separate thread.
     /// It uses the pool from the current active connection, which should have the correct database context.
     pub fn get_pooled_connection(&self) -> Result<PooledConn> {
+        if self.pending.contains_key(&self.current_connection) {
+            return Err(self.pending_error(&self.current_connection));
+        }
         self.connections
             .get(&self.current_connection)
             .ok_or_else(|| anyhow!("Current connection '{}' not found", self.current_connection))?
@@ -55,6 +173,9 @@ impl ConnectionManager {
 
     /// Get the current active database connection
     pub fn current_database(&mut self) -> Result<&mut Database> {
+        if self.pending.contains_key(&self.current_connection) {
+            return Err(self.pending_error(&self.current_connection));
+        }
         self.connections
             .get_mut(&self.current_connection)
             .ok_or_else(|| anyhow!("Current connection '{}' not found", self.current_connection))
@@ -67,7 +188,8 @@ impl ConnectionManager {
         let connection_info = self.build_connection_info(&connect_params)?;
 
         // Create new database connection (always MySQL)
-        let database = create_database_with_retry("mysql", &connection_info, self.max_retries)?;
+        let mut database = create_database_with_retry("mysql", &connection_info, self.max_retries)?;
+        database.set_value_format(self.value_format.clone());
 
         // Store the connection and switch to it
         self.connections
@@ -81,9 +203,18 @@ impl ConnectionManager {
         Ok(())
     }
 
+    /// Render result values of all connections, including later ones, with `format`.
+    /// Connections running a --send statement get it when reaped.
+    pub fn set_value_format(&mut self, format: ValueFormat) {
+        for database in self.connections.values_mut() {
+            database.set_value_format(format.clone());
+        }
+        self.value_format = format;
+    }
+
     /// Switch to an existing connection
     pub fn switch_connection(&mut self, conn_name: &str) -> Result<()> {
-        if !self.connections.contains_key(conn_name) {
+        if !self.connections.contains_key(conn_name) && !self.pending.contains_key(conn_name) {
             return Err(anyhow!("Connection '{}' does not exist", conn_name));
         }
 
@@ -98,6 +229,10 @@ impl ConnectionManager {
             return Err(anyhow!("Cannot disconnect the default connection"));
         }
 
+        if self.pending.contains_key(conn_name) {
+            return Err(self.pending_error(conn_name));
+        }
+
         if !self.connections.contains_key(conn_name) {
             return Err(anyhow!("Connection '{}' does not exist", conn_name));
         }
@@ -250,6 +385,8 @@ mod tests {
             current_connection: "default".to_string(),
             default_connection_info: create_test_connection_info(),
             max_retries: 1,
+            pending: HashMap::new(),
+            value_format: ValueFormat::default(),
         };
 
         let params = manager
@@ -270,6 +407,8 @@ mod tests {
             current_connection: "default".to_string(),
             default_connection_info: create_test_connection_info(),
             max_retries: 1,
+            pending: HashMap::new(),
+            value_format: ValueFormat::default(),
         };
 
         let params = manager
@@ -287,6 +426,8 @@ mod tests {
             current_connection: "default".to_string(),
             default_connection_info: create_test_connection_info(),
             max_retries: 1,
+            pending: HashMap::new(),
+            value_format: ValueFormat::default(),
         };
 
         let params = ConnectParams {
@@ -306,6 +447,23 @@ mod tests {
         assert_eq!(info.port, 3306);
     }
 
+    #[test]
+    fn test_reap_without_send() {
+        let mut manager = ConnectionManager {
+            connections: HashMap::new(),
+            current_connection: "default".to_string(),
+            default_connection_info: create_test_connection_info(),
+            max_retries: 1,
+            pending: HashMap::new(),
+            value_format: ValueFormat::default(),
+        };
+
+        assert!(!manager.has_pending("default"));
+        assert!(manager.pending_connections().is_empty());
+        let err = manager.reap("default").unwrap_err();
+        assert!(err.to_string().contains("No pending statement"));
+    }
+
     #[test]
     fn test_connection_manager_interface() {
         // This test would require actual database connections
@@ -320,6 +478,8 @@ mod tests {
             current_connection: "default".to_string(),
             default_connection_info: connection_info,
             max_retries: 1,
+            pending: HashMap::new(),
+            value_format: ValueFormat::default(),
         };
 
         // Test list connections
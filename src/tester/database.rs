@@ -6,6 +6,8 @@
 use anyhow::{anyhow, Result};
 use log::{debug, error, info, trace, warn};
 use std::time::Duration;
+use crate::tester::value_format::ValueFormat;
+use crate::util::charset::{parse_charset, Charset};
 use crate::util::memory_pool::{get_row_data, get_string_vec, PooledRowData};
 
 /// 网络超时配置常量
@@ -68,6 +70,35 @@ fn detect_operation_type(sql: &str) -> OperationType {
     }
 }
 
+/// ER_UNSUPPORTED_PS: the statement is not supported by the prepared statement protocol
+const ER_UNSUPPORTED_PS: u16 = 1295;
+
+/// Statements sent through prepare/execute under --ps-protocol (like mysqltest's
+/// ps_re); everything else keeps using the text protocol
+fn is_preparable(sql: &str) -> bool {
+    const PREPARABLE: &[&str] = &[
+        "SELECT", "INSERT", "UPDATE", "DELETE", "REPLACE", "DO", "CALL", "WITH", "SET", "SHOW",
+        "CREATE", "DROP", "ALTER", "TRUNCATE", "RENAME", "ANALYZE", "OPTIMIZE", "REPAIR",
+        "CHECKSUM", "GRANT", "REVOKE",
+    ];
+    let keyword = sql
+        .trim_start()
+        .split(|c: char| c.is_whitespace() || c == '(')
+        .next()
+        .unwrap_or("")
+        .to_ascii_uppercase();
+    PREPARABLE.contains(&keyword.as_str())
+}
+
+/// A plain SELECT cannot change the session's default database
+fn is_select(sql: &str) -> bool {
+    sql.trim_start()
+        .trim_start_matches(|c: char| c == '(' || c.is_whitespace())
+        .split(|c: char| c.is_whitespace() || c == '(')
+        .next()
+        .is_some_and(|keyword| keyword.eq_ignore_ascii_case("SELECT"))
+}
+
 /// Database connection abstraction
 #[derive(Debug)]
 pub enum Database {
@@ -104,6 +135,41 @@ impl Database {
         }
     }
 
+    /// Execute a query and return the rows together with the column metadata
+    pub fn query_with_metadata(&mut self, sql: &str) -> Result<QueryResult> {
+        match self {
+            Database::MySQL(db) => db.query_with_metadata(sql),
+        }
+    }
+
+    /// Set the connection charset results are decoded with (--character_set)
+    pub fn set_charset(&mut self, charset: Charset) -> Result<()> {
+        match self {
+            Database::MySQL(db) => db.set_charset(charset),
+        }
+    }
+
+    /// Charset results of this connection are decoded with
+    pub fn charset(&self) -> Charset {
+        match self {
+            Database::MySQL(db) => db.charset,
+        }
+    }
+
+    /// How result values are rendered (--value_format)
+    pub fn set_value_format(&mut self, format: ValueFormat) {
+        match self {
+            Database::MySQL(db) => db.value_format = format,
+        }
+    }
+
+    /// Like `query_with_metadata`, but through prepare/execute where possible (--ps-protocol)
+    pub fn query_prepared(&mut self, sql: &str) -> Result<QueryResult> {
+        match self {
+            Database::MySQL(db) => db.query_prepared(sql),
+        }
+    }
+
     /// Execute a query without returning results
     pub fn execute(&mut self, sql: &str) -> Result<()> {
         match self {
@@ -137,8 +203,115 @@ impl Database {
             Database::MySQL(db) => db.get_pooled_connection(),
         }
     }
+
+    /// Server version from the connection handshake, e.g. `8.0.33`
+    pub fn server_version(&mut self) -> Result<String> {
+        match self {
+            Database::MySQL(db) => db.server_version(),
+        }
+    }
+
+    /// Server thread id of the connection statements run on (for KILL QUERY)
+    pub fn connection_id(&mut self) -> Result<u32> {
+        match self {
+            Database::MySQL(db) => db.connection_id(),
+        }
+    }
+
+    /// Default database of the session (`DATABASE()`), `None` when there is none
+    pub fn current_schema(&mut self) -> Result<Option<String>> {
+        match self {
+            Database::MySQL(db) => db.current_schema(),
+        }
+    }
+
+    /// Connection pool, for side connections running in other threads
+    pub fn pool(&self) -> mysql::Pool {
+        match self {
+            Database::MySQL(db) => db.pool.clone(),
+        }
+    }
 }
 
+/// Metadata of a single result column
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnMeta {
+    pub name: String,
+    pub column_type: mysql::consts::ColumnType,
+    /// Schema, table and original names as sent by the server (for --enable_metadata)
+    pub schema: String,
+    pub table: String,
+    pub org_table: String,
+    pub org_name: String,
+    pub length: u32,
+    pub flags: mysql::consts::ColumnFlags,
+    pub decimals: u8,
+    /// Collation id of the column (`Charsetnr` in mysqltest output)
+    pub charset: u16,
+}
+
+impl From<&mysql::Column> for ColumnMeta {
+    fn from(column: &mysql::Column) -> Self {
+        ColumnMeta {
+            name: column.name_str().into_owned(),
+            column_type: column.column_type(),
+            schema: column.schema_str().into_owned(),
+            table: column.table_str().into_owned(),
+            org_table: column.org_table_str().into_owned(),
+            org_name: column.org_name_str().into_owned(),
+            length: column.column_length(),
+            flags: column.flags(),
+            decimals: column.decimals(),
+            charset: column.character_set(),
+        }
+    }
+}
+
+/// Statement status from the server (for --enable_info).
+/// For result sets `affected_rows` is the row count, like `mysql_affected_rows()`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecutionInfo {
+    pub affected_rows: u64,
+    /// AUTO_INCREMENT value generated by the statement, 0 if none
+    pub last_insert_id: u64,
+    /// Info string such as `Rows matched: 1  Changed: 1  Warnings: 0`, may be empty
+    pub info: String,
+    /// Number of warnings, only known for statements without a result set
+    pub warning_count: u16,
+}
+
+/// Rows of a result set together with its column metadata.
+/// `columns` is empty for statements that do not produce a result set (INSERT, DDL...).
+/// Rows are plain vectors rather than pooled ones so a result can be handed
+/// across threads (see `--send` / `--reap`).
+#[derive(Debug, Default)]
+pub struct QueryResult {
+    pub columns: Vec<ColumnMeta>,
+    pub rows: Vec<Vec<String>>,
+    pub execution_info: ExecutionInfo,
+    /// Result sets after the first one: the following statements of a
+    /// multi-statement query, or further SELECTs of a `CALL`
+    pub more_results: Vec<QueryResult>,
+    /// Error of a later statement of a multi-statement query. The server stops
+    /// there; the result sets before it are kept.
+    pub error: Option<anyhow::Error>,
+}
+
+impl QueryResult {
+    /// Column names in result order
+    pub fn column_names(&self) -> Vec<String> {
+        self.columns.iter().map(|c| c.name.clone()).collect()
+    }
+
+    /// This result set followed by the further ones
+    pub fn result_sets(&self) -> impl Iterator<Item = &QueryResult> {
+        std::iter::once(self).chain(&self.more_results)
+    }
+}
+
+/// Columns, rows and status of one result set as read from the server
+type RawResultSet = (Vec<ColumnMeta>, Vec<mysql::Row>, ExecutionInfo);
+
 /// Connection information structure
 #[derive(Debug, Clone)]
 pub struct ConnectionInfo {
@@ -157,6 +330,12 @@ pub struct MySQLDatabase {
     info: ConnectionInfo,
     /// 可复用的连接。串行执行场景下复用单个 `PooledConn` 可避免每条语句重新握手造成的额外延迟。
     conn: Option<mysql::PooledConn>,
+    /// 结果值的渲染方式（--value_format）
+    value_format: ValueFormat,
+    /// 连接字符集，结果字节按其解码（--character-set / --character_set / SET NAMES）
+    charset: Charset,
+    /// 最近读取的 DATABASE() 及其所属连接 id；执行非 SELECT 语句后失效
+    schema: Option<(u32, Option<String>)>,
 }
 
 impl MySQLDatabase {
@@ -177,15 +356,23 @@ impl MySQLDatabase {
         }
 
         // Parse additional parameters
+        let mut charset = Charset::default();
         if !info.params.is_empty() {
             for param in info.params.split('&') {
                 if let Some((key, value)) = param.split_once('=') {
                     debug!("MySQL parameter: {}={}", key, value);
                     // Handle specific MySQL parameters as needed
                     // This can be extended to support more MySQL-specific options
+                    if key == "charset" {
+                        charset = parse_charset(value)?;
+                    }
                 }
             }
         }
+        // 每个新建的池连接都设置连接字符集
+        if !charset.is_utf8() {
+            opts = opts.init(vec![charset.session_statement()]);
+        }
 
         // 使用默认连接池配置（MySQL 26.0.0 不支持手动配置）
         let pool = mysql::Pool::new(opts)?;
@@ -194,6 +381,9 @@ impl MySQLDatabase {
             pool,
             info: info.clone(),
             conn: None,
+            value_format: ValueFormat::default(),
+            charset,
+            schema: None,
         })
     }
 
@@ -262,10 +452,41 @@ impl MySQLDatabase {
     }
 
     pub fn query_with_operation_type(&mut self, sql: &str, op_type: OperationType) -> Result<PooledRowData> {
-        use mysql::prelude::Queryable;
+        let result = self.query_with_columns(sql, op_type, false)?;
+        if let Some(e) = result.error {
+            return Err(e);
+        }
+        let mut rows = get_row_data();
+        rows.extend(result.rows);
+        Ok(rows)
+    }
+
+    pub fn query_with_metadata(&mut self, sql: &str) -> Result<QueryResult> {
+        self.query_with_protocol(sql, false)
+    }
+
+    /// Run a statement through prepare/execute (binary protocol). Statements
+    /// that cannot be prepared are run with the text protocol instead.
+    pub fn query_prepared(&mut self, sql: &str) -> Result<QueryResult> {
+        self.query_with_protocol(sql, true)
+    }
 
+    fn query_with_protocol(&mut self, sql: &str, prepared: bool) -> Result<QueryResult> {
+        let op_type = detect_operation_type(sql);
+        self.query_with_columns(sql, op_type, prepared)
+    }
+
+    fn query_with_columns(
+        &mut self,
+        sql: &str,
+        op_type: OperationType,
+        prepared: bool,
+    ) -> Result<QueryResult> {
         trace!("-> exec ({:?}): {}", op_type, sql);
         let connection_timeout = get_connection_timeout_for_operation(op_type);
+        if !is_select(sql) {
+            self.schema = None;
+        }
         
         
         if self.conn.is_none() {
@@ -273,12 +494,12 @@ impl MySQLDatabase {
         }
 
 
-        let result: Result<Vec<mysql::Row>, mysql::Error> = {
+        let result = {
             let conn_ref = self.conn.as_mut().unwrap();
-            conn_ref.query(sql)
+            Self::fetch_rows(conn_ref, sql, prepared)
         };
 
-        let rows: Vec<mysql::Row> = match result {
+        let (sets, error) = match result {
             Ok(rows) => Ok(rows),
             Err(e) => {
                 if let mysql::Error::IoError(ref io_err) = e {
@@ -294,9 +515,9 @@ impl MySQLDatabase {
                         if let Ok(new_conn) = self.get_pooled_connection_with_timeout(connection_timeout) {
                             let mut new_conn = new_conn;
                             // 将新连接放入缓存，供后续复用
-                            let new_rows: Vec<mysql::Row> = new_conn.query(sql)?;
+                            let (new_sets, new_error) = Self::fetch_rows(&mut new_conn, sql, prepared)?;
                             self.conn = Some(new_conn);
-                            return self.process_rows(new_rows);
+                            return self.build_result(new_sets, new_error);
                         }
                     }
                 }
@@ -305,7 +526,118 @@ impl MySQLDatabase {
             }
         }?;
 
-        self.process_rows(rows)
+        self.track_charset(sql);
+        self.build_result(sets, error)
+    }
+
+    /// Turn the result sets read from the server into a QueryResult holding
+    /// the first set, the further ones and the error that ended the batch
+    fn build_result(&self, sets: Vec<RawResultSet>, error: Option<mysql::Error>) -> Result<QueryResult> {
+        let mut results = Vec::with_capacity(sets.len());
+        for (columns, rows, execution_info) in sets {
+            results.push(QueryResult {
+                columns,
+                rows: self.process_rows(rows)?.take(),
+                execution_info,
+                ..QueryResult::default()
+            });
+        }
+        let mut results = results.into_iter();
+        let mut first = results.next().unwrap_or_default();
+        first.more_results = results.collect();
+        first.error = error.map(anyhow::Error::from);
+        Ok(first)
+    }
+
+    /// After `SET NAMES <charset>` results arrive in that charset
+    fn track_charset(&mut self, sql: &str) {
+        let mut words = sql.split_whitespace();
+        let is_set_names = words.next().is_some_and(|w| w.eq_ignore_ascii_case("SET"))
+            && words.next().is_some_and(|w| w.eq_ignore_ascii_case("NAMES"));
+        if !is_set_names {
+            return;
+        }
+        let name = words.next().unwrap_or("").trim_end_matches(';');
+        match parse_charset(name) {
+            Ok(charset) => {
+                debug!("Results are now decoded as {}", charset.name());
+                self.charset = charset;
+            }
+            Err(e) => debug!("Keeping result charset {}: {}", self.charset.name(), e),
+        }
+    }
+
+    /// Set the connection charset (--character_set)
+    pub fn set_charset(&mut self, charset: Charset) -> Result<()> {
+        self.execute(&charset.session_statement())?;
+        self.charset = charset;
+        Ok(())
+    }
+
+    /// Run a statement and collect all of its result sets.
+    /// With `prepared` the statement goes through prepare/execute when possible.
+    fn fetch_rows(
+        conn: &mut mysql::PooledConn,
+        sql: &str,
+        prepared: bool,
+    ) -> Result<(Vec<RawResultSet>, Option<mysql::Error>), mysql::Error> {
+        use mysql::prelude::Queryable;
+
+        if prepared && is_preparable(sql) {
+            match conn.prep(sql) {
+                // `?` in the statement text would need parameters we do not have
+                Ok(stmt) if stmt.num_params() == 0 => {
+                    return Self::collect_result(conn.exec_iter(&stmt, ())?);
+                }
+                Ok(_) => debug!("Statement has placeholders, using text protocol: {}", sql),
+                Err(mysql::Error::MySqlError(ref e)) if e.code == ER_UNSUPPORTED_PS => {
+                    debug!("Statement cannot be prepared, using text protocol: {}", sql)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Self::collect_result(conn.query_iter(sql)?)
+    }
+
+    /// Collect the columns and rows of every result set of either protocol:
+    /// one per statement of a multi-statement query, several for a `CALL`.
+    /// Columns are read from the result metadata, so they are available even
+    /// when the result set is empty. An error of the first statement is
+    /// returned as is; an error of a later one ends the batch and is returned
+    /// with the result sets before it.
+    fn collect_result<P: mysql::prelude::Protocol>(
+        mut result: mysql::QueryResult<'_, '_, '_, P>,
+    ) -> Result<(Vec<RawResultSet>, Option<mysql::Error>), mysql::Error> {
+        let mut sets = Vec::new();
+        loop {
+            let columns: Vec<ColumnMeta> = result
+                .columns()
+                .as_ref()
+                .iter()
+                .map(ColumnMeta::from)
+                .collect();
+            // OK packet status is only available before the set is consumed
+            let mut execution_info = ExecutionInfo {
+                affected_rows: result.affected_rows(),
+                last_insert_id: result.last_insert_id().unwrap_or(0),
+                info: result.info_str().into_owned(),
+                warning_count: result.warnings(),
+            };
+            let Some(set) = result.iter() else {
+                break;
+            };
+            let rows = match set.collect::<Result<Vec<mysql::Row>, mysql::Error>>() {
+                Ok(rows) => rows,
+                Err(e) if sets.is_empty() => return Err(e),
+                Err(e) => return Ok((sets, Some(e))),
+            };
+            if !columns.is_empty() {
+                execution_info.affected_rows = rows.len() as u64;
+            }
+            sets.push((columns, rows, execution_info));
+        }
+        Ok((sets, None))
     }
 
     /// Helper function to process rows into PooledRowData (memory pool optimized)
@@ -329,24 +661,9 @@ impl MySQLDatabase {
                 row_data.reserve(row.len());
             }
             
-            for idx in 0..row.len() {
+            for (idx, column) in row.columns_ref().iter().enumerate() {
                 let val = row.as_ref(idx).unwrap_or(&Value::NULL);
-                let cell = match val {
-                    Value::NULL => "NULL".to_string(),
-                    Value::Bytes(b) => String::from_utf8_lossy(b).into_owned(),
-                    Value::Int(n) => n.to_string(),
-                    Value::UInt(n) => n.to_string(),
-                    Value::Float(f) => (*f as f64).to_string(),
-                    Value::Double(d) => d.to_string(),
-                    Value::Date(y, m, d, hh, mm, ss, _us) => {
-                        format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", y, m, d, hh, mm, ss)
-                    }
-                    Value::Time(_neg, d, hh, mm, ss, _us) => {
-                        let total_hours = (*d as u32) * 24 + (*hh as u32);
-                        format!("{:02}:{:02}:{:02}", total_hours, mm, ss)
-                    }
-                };
-                row_data.push(cell);
+                row_data.push(self.value_format.format(val, column, self.charset));
             }
             
             // Convert pooled string vec to regular vec before pushing to result
@@ -355,6 +672,43 @@ impl MySQLDatabase {
         Ok(result_vec)
     }
 
+    pub fn server_version(&mut self) -> Result<String> {
+        if self.conn.is_none() {
+            self.conn = Some(self.get_pooled_connection()?);
+        }
+        let (major, minor, patch) = self.conn.as_ref().unwrap().server_version();
+        Ok(format!("{}.{}.{}", major, minor, patch))
+    }
+
+    pub fn connection_id(&mut self) -> Result<u32> {
+        if self.conn.is_none() {
+            self.conn = Some(self.get_pooled_connection()?);
+        }
+        Ok(self.conn.as_ref().unwrap().connection_id())
+    }
+
+    /// `DATABASE()` of the session. Cached until a statement other than a plain
+    /// SELECT runs or the connection is replaced, so wrapping consecutive SELECTs
+    /// (--view-protocol / --sp-protocol) does not query it every time.
+    pub fn current_schema(&mut self) -> Result<Option<String>> {
+        use mysql::prelude::Queryable;
+
+        let connection_id = self.connection_id()?;
+        if let Some((cached_id, schema)) = &self.schema {
+            if *cached_id == connection_id {
+                return Ok(schema.clone());
+            }
+        }
+        let schema = self
+            .conn
+            .as_mut()
+            .unwrap()
+            .query_first::<Option<String>, _>("SELECT DATABASE()")?
+            .flatten();
+        self.schema = Some((connection_id, schema.clone()));
+        Ok(schema)
+    }
+
     pub fn execute(&mut self, sql: &str) -> Result<()> {
         let op_type = detect_operation_type(sql);
         self.execute_with_operation_type(sql, op_type)
@@ -365,6 +719,9 @@ impl MySQLDatabase {
 
         trace!("-> execute ({:?}): {}", op_type, sql);
         let connection_timeout = get_connection_timeout_for_operation(op_type);
+        if !is_select(sql) {
+            self.schema = None;
+        }
         
         if self.conn.is_none() {
             self.conn = Some(self.get_pooled_connection_with_timeout(connection_timeout)?);
@@ -784,4 +1141,19 @@ mod tests {
 
         assert_eq!(formatted_info, expected_info);
     }
+    #[test]
+    fn test_is_preparable() {
+        assert!(is_preparable("SELECT 1"));
+        assert!(is_preparable("  insert INTO t VALUES (1)"));
+        assert!(!is_preparable("LOCK TABLES t WRITE"));
+        assert!(!is_preparable("HANDLER t OPEN"));
+    }
+
+    #[test]
+    fn test_is_select() {
+        assert!(is_select("select * from t1"));
+        assert!(is_select(" (SELECT 1) UNION (SELECT 2)"));
+        assert!(!is_select("USE test"));
+        assert!(!is_select("SELECTION"));
+    }
 }
@@ -3,55 +3,58 @@
 //! This module provides error mapping from MySQL error codes to standardized
 //! error representations, making it easier to handle expected errors in test cases.
 
-use std::collections::HashMap;
+use crate::tester::error_codes::ERROR_CODES;
+use mysql::DriverError;
+
+/// SQLSTATE reported for client side errors, as the mysql client does
+const CLIENT_SQLSTATE: &str = "HY000";
 
 /// MySQL error code mappings
 /// Based on the MySQL error codes from the original Go implementation
 pub struct MySQLErrorHandler {
-    /// Error name to error number mapping
-    error_name_map: HashMap<String, u16>,
+    /// Error name to error number mapping (`ER_*` server and `CR_*` client errors)
+    error_name_map: &'static phf::Map<&'static str, u16>,
+}
+
+/// One entry of an `--error` list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpectedError {
+    /// `0`: any error is accepted
+    Any,
+    /// Numeric code or error name resolved to its code
+    Code(u16),
+    /// `S42S02` style SQLSTATE expectation
+    SqlState(String),
 }
 
 impl MySQLErrorHandler {
     /// Create a new MySQL error handler with standard error mappings
     pub fn new() -> Self {
-        let mut error_name_map = HashMap::new();
-
-        // Common MySQL error codes
-        error_name_map.insert("ER_DUP_KEY".to_string(), 1022);
-        error_name_map.insert("ER_DUP_ENTRY".to_string(), 1062);
-        error_name_map.insert("ER_NO_SUCH_TABLE".to_string(), 1146);
-        error_name_map.insert("ER_TABLE_EXISTS_ERROR".to_string(), 1050);
-        error_name_map.insert("ER_BAD_FIELD_ERROR".to_string(), 1054);
-        error_name_map.insert("ER_PARSE_ERROR".to_string(), 1064);
-        error_name_map.insert("ER_NO_SUCH_INDEX".to_string(), 1082);
-        error_name_map.insert("ER_KEY_COLUMN_DOES_NOT_EXITS".to_string(), 1072);
-        error_name_map.insert("ER_WRONG_VALUE_COUNT_ON_ROW".to_string(), 1136);
-        error_name_map.insert("ER_ACCESS_DENIED_ERROR".to_string(), 1045);
-        error_name_map.insert("ER_DBACCESS_DENIED_ERROR".to_string(), 1044);
-        error_name_map.insert("ER_TABLEACCESS_DENIED_ERROR".to_string(), 1142);
-        error_name_map.insert("ER_COLUMNACCESS_DENIED_ERROR".to_string(), 1143);
-        error_name_map.insert("ER_DATA_TOO_LONG".to_string(), 1406);
-        error_name_map.insert("ER_WARN_DATA_OUT_OF_RANGE".to_string(), 1264);
-        error_name_map.insert("ER_DIVISION_BY_ZERO".to_string(), 1365);
-        error_name_map.insert("ER_BAD_NULL_ERROR".to_string(), 1048);
-        error_name_map.insert("ER_NON_UNIQ_ERROR".to_string(), 1052);
-        error_name_map.insert("ER_WRONG_AUTO_KEY".to_string(), 1075);
-        error_name_map.insert("ER_WRONG_VALUE_FOR_VAR".to_string(), 1231);
-        error_name_map.insert("ER_UNKNOWN_SYSTEM_VARIABLE".to_string(), 1193);
-        error_name_map.insert("ER_CANT_DROP_FIELD_OR_KEY".to_string(), 1091);
-        error_name_map.insert("ER_TOO_MANY_KEYS".to_string(), 1069);
-        error_name_map.insert("ER_TOO_MANY_KEY_PARTS".to_string(), 1070);
-        error_name_map.insert("ER_TOO_LONG_KEY".to_string(), 1071);
-        error_name_map.insert("ER_BLOB_USED_AS_KEY".to_string(), 1073);
-        error_name_map.insert("ER_TOO_BIG_FIELDLENGTH".to_string(), 1074);
-        error_name_map.insert("ER_WRONG_FIELD_SPEC".to_string(), 1063);
-        error_name_map.insert("ER_EMPTY_QUERY".to_string(), 1065);
-        error_name_map.insert("ER_NONUNIQ_TABLE".to_string(), 1066);
-        error_name_map.insert("ER_INVALID_DEFAULT".to_string(), 1067);
-        error_name_map.insert("ER_MULTIPLE_PRI_KEY".to_string(), 1068);
-
-        Self { error_name_map }
+        Self {
+            error_name_map: &ERROR_CODES,
+        }
+    }
+
+    /// Parse one `--error` value: `0`, a code, an error name or `S<SQLSTATE>`.
+    /// Returns None for names that are not in the error table; `--error`
+    /// warns about those when the command is parsed.
+    pub fn parse_expected(&self, expected: &str) -> Option<ExpectedError> {
+        let expected = expected.trim();
+
+        if expected == "0" {
+            return Some(ExpectedError::Any);
+        }
+        if let Ok(code) = expected.parse::<u16>() {
+            return Some(ExpectedError::Code(code));
+        }
+        if let Some(state) = expected.strip_prefix('S') {
+            if state.len() == 5 && state.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Some(ExpectedError::SqlState(state.to_ascii_uppercase()));
+            }
+        }
+        self.error_name_map
+            .get(expected)
+            .map(|&code| ExpectedError::Code(code))
     }
 
     /// Check if an error matches expected error conditions
@@ -61,51 +64,51 @@ impl MySQLErrorHandler {
             return false;
         }
 
-        // Extract MySQL error code from the error
-        let error_code = match error {
-            mysql::Error::MySqlError(mysql_err) => Some(mysql_err.code),
-            _ => None,
-        };
+        let error_code = self.get_error_code(error);
+        let sqlstate = self.get_sqlstate(error);
 
-        for expected in expected_errors {
-            let expected = expected.trim();
-
-            // Special case: "0" means accept any error
-            if expected == "0" {
-                return true;
-            }
-
-            // Try to parse as error number
-            if let Ok(expected_code) = expected.parse::<u16>() {
-                if let Some(code) = error_code {
-                    if code == expected_code {
-                        return true;
-                    }
-                }
-            }
+        expected_errors
+            .iter()
+            .filter_map(|expected| self.parse_expected(expected))
+            .any(|expected| match expected {
+                ExpectedError::Any => true,
+                ExpectedError::Code(code) => error_code == Some(code),
+                ExpectedError::SqlState(state) => sqlstate.eq_ignore_ascii_case(&state),
+            })
+    }
 
-            // Try to match by error name
-            if let Some(&mapped_code) = self.error_name_map.get(expected) {
-                if let Some(code) = error_code {
-                    if code == mapped_code {
-                        return true;
-                    }
+    /// Get error code from MySQL error. Driver and I/O failures are reported
+    /// with the matching client (`CR_*`) error code.
+    pub fn get_error_code(&self, error: &mysql::Error) -> Option<u16> {
+        let code = match error {
+            mysql::Error::MySqlError(mysql_err) => return Some(mysql_err.code),
+            mysql::Error::IoError(_) => "CR_SERVER_LOST",
+            mysql::Error::CodecError(_) => "CR_MALFORMED_PACKET",
+            mysql::Error::DriverError(driver_err) => match driver_err {
+                DriverError::ConnectTimeout | DriverError::CouldNotConnect(_) => {
+                    "CR_CONN_HOST_ERROR"
                 }
-            }
-        }
-
-        false
+                DriverError::PacketOutOfSync => "CR_COMMANDS_OUT_OF_SYNC",
+                DriverError::PacketTooLarge => "CR_NET_PACKET_TOO_LARGE",
+                DriverError::UnexpectedPacket => "CR_MALFORMED_PACKET",
+                DriverError::MismatchedStmtParams(..) => "CR_PARAMS_NOT_BOUND",
+                _ => "CR_UNKNOWN_ERROR",
+            },
+            _ => return None,
+        };
+        self.error_name_map.get(code).copied()
     }
 
-    /// Get error code from MySQL error
-    pub fn get_error_code(&self, error: &mysql::Error) -> Option<u16> {
+    /// SQLSTATE of the error; client side errors use HY000
+    pub fn get_sqlstate<'a>(&self, error: &'a mysql::Error) -> &'a str {
         match error {
-            mysql::Error::MySqlError(mysql_err) => Some(mysql_err.code),
-            _ => None,
+            mysql::Error::MySqlError(mysql_err) => &mysql_err.state,
+            _ => CLIENT_SQLSTATE,
         }
     }
 
-    /// Format error message for output
+    /// Format error message for output, e.g.
+    /// `ERROR 1146 (42S02): Table 'test.t1' doesn't exist`
     pub fn format_error(&self, error: &mysql::Error) -> String {
         match error {
             mysql::Error::MySqlError(mysql_err) => {
@@ -114,10 +117,10 @@ impl MySQLErrorHandler {
                     mysql_err.code, mysql_err.state, mysql_err.message
                 )
             }
-            mysql::Error::DriverError(driver_err) => {
-                format!("ERROR (Driver): {}", driver_err)
-            }
-            _ => format!("ERROR: {}", error),
+            _ => match self.get_error_code(error) {
+                Some(code) => format!("ERROR {} ({}): {}", code, CLIENT_SQLSTATE, error),
+                None => format!("ERROR: {}", error),
+            },
         }
     }
 }
@@ -171,6 +174,15 @@ impl ErrorCheckResult {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tester::error_codes;
+
+    fn server_error(code: u16, state: &str) -> mysql::Error {
+        mysql::Error::MySqlError(mysql::MySqlError {
+            state: state.to_string(),
+            message: "Table 'test.t1' doesn't exist".to_string(),
+            code,
+        })
+    }
 
     #[test]
     fn test_error_handler_creation() {
@@ -179,6 +191,70 @@ mod tests {
         assert_eq!(handler.error_name_map.get("ER_DUP_KEY"), Some(&1022));
     }
 
+    #[test]
+    fn test_parse_expected() {
+        let handler = MySQLErrorHandler::new();
+        assert_eq!(handler.parse_expected("0"), Some(ExpectedError::Any));
+        assert_eq!(handler.parse_expected("1146"), Some(ExpectedError::Code(1146)));
+        assert_eq!(
+            handler.parse_expected("ER_LOCK_DEADLOCK"),
+            Some(ExpectedError::Code(1213))
+        );
+        assert_eq!(
+            handler.parse_expected("ER_LOCK_WAIT_TIMEOUT"),
+            Some(ExpectedError::Code(1205))
+        );
+        assert_eq!(
+            handler.parse_expected("CR_SERVER_LOST"),
+            Some(ExpectedError::Code(2013))
+        );
+        assert_eq!(
+            handler.parse_expected("S42S02"),
+            Some(ExpectedError::SqlState("42S02".to_string()))
+        );
+        assert_eq!(handler.parse_expected("ER_NOT_A_REAL_ERROR"), None);
+    }
+
+    #[test]
+    fn test_error_name_lookup() {
+        assert_eq!(error_codes::error_name(1146), Some("ER_NO_SUCH_TABLE"));
+        assert_eq!(error_codes::error_name(2013), Some("CR_SERVER_LOST"));
+        assert_eq!(error_codes::error_name(1265), Some("WARN_DATA_TRUNCATED"));
+        assert_eq!(error_codes::error_name(9999), None);
+    }
+
+    #[test]
+    fn test_check_expected_error() {
+        let handler = MySQLErrorHandler::new();
+        let error = server_error(1146, "42S02");
+        let check = |expected: &[&str]| {
+            let expected: Vec<String> = expected.iter().map(|s| s.to_string()).collect();
+            handler.check_expected_error(&error, &expected)
+        };
+
+        assert!(check(&["1146"]));
+        assert!(check(&["ER_NO_SUCH_TABLE"]));
+        assert!(check(&["S42S02"]));
+        assert!(check(&["ER_DUP_ENTRY", "S42S02"]));
+        assert!(check(&["0"]));
+        assert!(!check(&["S23000"]));
+        assert!(!check(&["ER_DUP_ENTRY"]));
+        assert!(!check(&[]));
+    }
+
+    #[test]
+    fn test_format_error() {
+        let handler = MySQLErrorHandler::new();
+        assert_eq!(
+            handler.format_error(&server_error(1146, "42S02")),
+            "ERROR 1146 (42S02): Table 'test.t1' doesn't exist"
+        );
+
+        let lost = mysql::Error::DriverError(DriverError::PacketOutOfSync);
+        assert!(handler.check_expected_error(&lost, &["CR_COMMANDS_OUT_OF_SYNC".to_string()]));
+        assert!(handler.format_error(&lost).starts_with("ERROR 2014 (HY000): "));
+    }
+
     #[test]
     fn test_error_check_result() {
         let result = ErrorCheckResult::NoError;
@@ -0,0 +1,23 @@
+//! Handler for the --character_set command.
+//!
+//! Syntax: `--character_set <name>` (e.g. `gbk`, `latin1`). Sets the charset
+//! of the current connection: string literals take it and results are
+//! returned in it and decoded accordingly.
+
+use crate::tester::command::Command;
+use crate::tester::tester::Tester;
+use crate::util::charset::parse_charset;
+use anyhow::Result;
+use log::debug;
+
+pub fn execute(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    let charset = parse_charset(&expanded)?;
+
+    tester
+        .connection_manager
+        .current_database()?
+        .set_charset(charset)?;
+    debug!("Connection character set set to {}", charset.name());
+    Ok(())
+}
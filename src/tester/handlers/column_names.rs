@@ -0,0 +1,52 @@
+//! Handlers for column name header control commands.
+
+use crate::tester::command::Command;
+use crate::tester::tester::Tester;
+use anyhow::Result;
+use log::debug;
+
+pub fn disable_column_names(tester: &mut Tester, _cmd: &Command) -> Result<()> {
+    tester.display_column_names = false;
+    debug!("Column names disabled");
+    Ok(())
+}
+
+pub fn enable_column_names(tester: &mut Tester, _cmd: &Command) -> Result<()> {
+    tester.display_column_names = true;
+    debug!("Column names enabled");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Args;
+    use crate::tester::tester::Tester;
+
+    fn create_test_args() -> Args {
+        Args {
+            host: "127.0.0.1".to_string(),
+            port: "3306".to_string(),
+            user: "root".to_string(),
+            passwd: "password".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[ignore = "Requires database connection - run with integration tests"]
+    fn test_toggle_column_names() {
+        let args = create_test_args();
+        let mut tester = Tester::new(args).unwrap();
+        let cmd = Command::default();
+
+        // Headers are on unless --legacy-result-format is given
+        assert!(tester.display_column_names);
+
+        disable_column_names(&mut tester, &cmd).unwrap();
+        assert!(!tester.display_column_names);
+
+        enable_column_names(&mut tester, &cmd).unwrap();
+        assert!(tester.display_column_names);
+    }
+}
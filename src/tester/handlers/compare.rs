@@ -0,0 +1,32 @@
+//! Handlers for tolerant result comparison (--compare, --enable_compare, --disable_compare).
+//!
+//! Syntax: `--compare float_epsilon=1e-9 ignore_trailing_ws case_insensitive`
+//! applies to the next statement only; `--enable_compare <options>` applies
+//! until `--disable_compare` or the end of the test.
+
+use crate::tester::command::Command;
+use crate::tester::compare::CompareOptions;
+use crate::tester::tester::Tester;
+use anyhow::Result;
+use log::debug;
+
+pub fn execute(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    let options = CompareOptions::parse(&expanded)?;
+    debug!("Compare options for next statement: {:?}", options);
+    tester.pending_compare = Some(options);
+    Ok(())
+}
+
+pub fn enable_compare(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    tester.compare_options = CompareOptions::parse(&expanded)?;
+    debug!("Compare options enabled: {:?}", tester.compare_options);
+    Ok(())
+}
+
+pub fn disable_compare(tester: &mut Tester, _cmd: &Command) -> Result<()> {
+    tester.compare_options = CompareOptions::default();
+    debug!("Compare options disabled");
+    Ok(())
+}
@@ -1,16 +1,56 @@
 //! Handler for the --error command.
 
 use crate::tester::command::Command;
+use crate::tester::error_handler::MySQLErrorHandler;
 use crate::tester::tester::Tester;
-use anyhow::Result;
-use log::debug;
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
 
 pub fn execute(tester: &mut Tester, cmd: &Command) -> Result<()> {
-    let error_spec = &cmd.args;
-    tester.expected_errors = error_spec
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .collect();
+    tester.expected_errors = parse_expected_errors(&cmd.args, cmd.line)?.into_iter().collect();
     debug!("Expected errors set to: {:?}", tester.expected_errors);
     Ok(())
 }
+
+/// Split an `--error` list. Unknown error names are kept but warned about, so
+/// a typo is visible in the log even though the expectation never matches.
+pub fn parse_expected_errors(error_spec: &str, line: usize) -> Result<Vec<String>> {
+    let handler = MySQLErrorHandler::new();
+    error_spec
+        .split(',')
+        .map(|s| s.trim().to_string())
+        .map(|expected| {
+            if expected.is_empty() {
+                return Err(anyhow!("Empty error code in --error at line {}", line));
+            }
+            if handler.parse_expected(&expected).is_none() {
+                warn!(
+                    "Unknown error code or name '{}' in --error at line {}",
+                    expected, line
+                );
+            }
+            Ok(expected)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_expected_errors() {
+        assert_eq!(
+            parse_expected_errors("1146, ER_WINDOW_NO_SUCH_WINDOW,S42S02,0", 1).unwrap(),
+            ["1146", "ER_WINDOW_NO_SUCH_WINDOW", "S42S02", "0"]
+        );
+        assert_eq!(
+            parse_expected_errors("ER_NO_SUCH_TABLE,ER_TYPO", 12).unwrap(),
+            ["ER_NO_SUCH_TABLE", "ER_TYPO"]
+        );
+        assert_eq!(
+            parse_expected_errors("1146,", 3).unwrap_err().to_string(),
+            "Empty error code in --error at line 3"
+        );
+    }
+}
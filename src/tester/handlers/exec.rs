@@ -57,7 +57,7 @@ pub fn execute(tester: &mut Tester, cmd: &Command) -> Result<()> {
                             };
 
                             // Apply regex replacements if any
-                            tester.apply_regex_replacements(&mut error_output);
+                            tester.apply_replacements(&mut error_output);
 
                             if tester.args.record {
                                 write!(tester.output_buffer, "{}", error_output)?;
@@ -116,7 +116,7 @@ pub fn execute(tester: &mut Tester, cmd: &Command) -> Result<()> {
                 let mut output_str = stdout_str;
 
                 // Apply regex replacements if any
-                tester.apply_regex_replacements(&mut output_str);
+                tester.apply_replacements(&mut output_str);
 
                 if tester.args.record {
                     write!(tester.output_buffer, "{}", output_str)?;
@@ -176,6 +176,21 @@ mod tests {
                 fail_fast: false,
                 parallel: 1,
                 max_connections: 0,
+                legacy_result_format: false,
+                max_loop_iterations: 10_000,
+                vardir: "var".to_string(),
+                mysql_client: "mysql".to_string(),
+                ps_protocol: false,
+                view_protocol: false,
+                sp_protocol: false,
+                query_timeout: None,
+                test_timeout: None,
+                max_diff_lines: 200,
+                continue_on_mismatch: false,
+                review: false,
+                record_pending: false,
+                character_set: None,
+                file_charset: None,
                 result_dir: "".to_string(),
             };
 
@@ -0,0 +1,257 @@
+//! Helpers for `foreach` loops.
+//!
+//! Syntax:
+//!
+//! ```text
+//! foreach $x in (a, b, 'c d') { ... }
+//! foreach $row in (SELECT id, name FROM t) { ... }
+//! ```
+//!
+//! A list binds `$x` to each item. A query is executed once when the loop
+//! starts; every row binds `$row_<column>` for each column and `$row` to the
+//! first column. The loop itself is driven by the tester's control flow.
+
+use crate::tester::variables::VariableContext;
+use anyhow::{anyhow, Result};
+
+/// Where the values of a foreach loop come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForeachSource {
+    /// Comma separated literal items, variables not yet expanded
+    List(String),
+    /// SQL statement whose result set is iterated
+    Query(String),
+}
+
+/// Parsed `$var in (...)` header of a foreach loop
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeachSpec {
+    /// Loop variable name without the leading `$`
+    pub var: String,
+    pub source: ForeachSource,
+}
+
+/// Parse the part after the `foreach` keyword. A trailing `{` is ignored.
+pub fn parse_foreach(spec: &str) -> Result<ForeachSpec> {
+    let invalid = || {
+        anyhow!(
+            "Invalid foreach: expected 'foreach $var in (<items or SELECT ...>)'. Got: {}",
+            spec.trim()
+        )
+    };
+
+    let spec = spec.trim();
+    let spec = spec.strip_suffix('{').unwrap_or(spec).trim_end();
+
+    let rest = spec.strip_prefix('$').ok_or_else(invalid)?;
+    let name_len = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(rest.len());
+    let (var, rest) = rest.split_at(name_len);
+    if var.is_empty() || !var.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        return Err(invalid());
+    }
+
+    let rest = rest.trim_start();
+    let rest = rest
+        .strip_prefix("in")
+        .filter(|r| r.starts_with(|c: char| c.is_whitespace() || c == '('))
+        .ok_or_else(invalid)?;
+    let inner = rest
+        .trim()
+        .strip_prefix('(')
+        .and_then(|r| r.strip_suffix(')'))
+        .ok_or_else(invalid)?
+        .trim();
+
+    let source = if is_query(inner) {
+        ForeachSource::Query(inner.to_string())
+    } else {
+        ForeachSource::List(inner.to_string())
+    };
+
+    Ok(ForeachSpec {
+        var: var.to_string(),
+        source,
+    })
+}
+
+/// Statements that produce a result set are iterated row by row
+fn is_query(source: &str) -> bool {
+    let keyword = source
+        .split(|c: char| c.is_whitespace() || c == '(')
+        .next()
+        .unwrap_or("")
+        .to_ascii_uppercase();
+    matches!(
+        keyword.as_str(),
+        "SELECT" | "SHOW" | "WITH" | "VALUES" | "TABLE" | "DESC" | "DESCRIBE" | "EXPLAIN"
+    )
+}
+
+/// Split a comma separated list, honouring single and double quotes.
+/// Whitespace around items is dropped; an empty list yields no items.
+pub fn split_list(input: &str) -> Result<Vec<String>> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    // Unquoted whitespace, kept only if more of the item follows
+    let mut pending_space = String::new();
+    let mut quote: Option<char> = None;
+    let mut started = false;
+
+    for ch in input.chars() {
+        if let Some(q) = quote {
+            if ch == q {
+                quote = None;
+            } else {
+                current.push(ch);
+            }
+            continue;
+        }
+
+        match ch {
+            ',' => {
+                items.push(std::mem::take(&mut current));
+                pending_space.clear();
+                started = false;
+            }
+            c if c.is_whitespace() => {
+                if started {
+                    pending_space.push(c);
+                }
+            }
+            c => {
+                current.push_str(&pending_space);
+                pending_space.clear();
+                if c == '\'' || c == '"' {
+                    quote = Some(c);
+                } else {
+                    current.push(c);
+                }
+                started = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(anyhow!("Unterminated quoted value in foreach list: {}", input));
+    }
+    if started || !items.is_empty() {
+        items.push(current);
+    }
+
+    Ok(items)
+}
+
+/// Variable name bound for a result column, e.g. (`row`, `user id`) -> `row_user_id`
+pub fn column_variable_name(var: &str, column: &str) -> String {
+    let column: String = column
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    format!("{}_{}", var, column)
+}
+
+/// Values of a running foreach loop, captured once when the loop starts
+#[derive(Debug, Clone)]
+pub struct ForeachIteration {
+    var: String,
+    /// Column names for query sources, empty for lists
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+    next_row: usize,
+}
+
+impl ForeachIteration {
+    /// Iterate over literal list items
+    pub fn from_list(var: &str, items: Vec<String>) -> Self {
+        Self {
+            var: var.to_string(),
+            columns: Vec::new(),
+            rows: items.into_iter().map(|item| vec![item]).collect(),
+            next_row: 0,
+        }
+    }
+
+    /// Iterate over the rows of a result set
+    pub fn from_rows(var: &str, columns: Vec<String>, rows: Vec<Vec<String>>) -> Self {
+        Self {
+            var: var.to_string(),
+            columns,
+            rows,
+            next_row: 0,
+        }
+    }
+
+    /// Bind the next row into `context`; returns false when all rows are consumed
+    pub fn bind_next(&mut self, context: &mut VariableContext) -> bool {
+        let Some(row) = self.rows.get(self.next_row) else {
+            return false;
+        };
+        self.next_row += 1;
+
+        context.set(self.var.clone(), row.first().cloned().unwrap_or_default());
+        for (column, value) in self.columns.iter().zip(row) {
+            context.set(column_variable_name(&self.var, column), value.clone());
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_foreach_list() {
+        let spec = parse_foreach("$x in (a, b, c) {").unwrap();
+        assert_eq!(spec.var, "x");
+        assert_eq!(spec.source, ForeachSource::List("a, b, c".to_string()));
+    }
+
+    #[test]
+    fn test_parse_foreach_query() {
+        let spec = parse_foreach("$row in (SELECT id, COUNT(*) AS n FROM t GROUP BY id)").unwrap();
+        assert_eq!(spec.var, "row");
+        assert_eq!(
+            spec.source,
+            ForeachSource::Query("SELECT id, COUNT(*) AS n FROM t GROUP BY id".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_foreach_invalid() {
+        assert!(parse_foreach("x in (a)").is_err());
+        assert!(parse_foreach("$x (a)").is_err());
+        assert!(parse_foreach("$x in a, b").is_err());
+        assert!(parse_foreach("$x inside (a)").is_err());
+    }
+
+    #[test]
+    fn test_split_list() {
+        assert_eq!(split_list("a, b ,c").unwrap(), vec!["a", "b", "c"]);
+        assert_eq!(split_list("'x, y', \"\"").unwrap(), vec!["x, y", ""]);
+        assert!(split_list("").unwrap().is_empty());
+        assert!(split_list("'oops").is_err());
+    }
+
+    #[test]
+    fn test_bind_rows() {
+        let mut context = VariableContext::new();
+        let mut iteration = ForeachIteration::from_rows(
+            "row",
+            vec!["id".to_string(), "user name".to_string()],
+            vec![
+                vec!["1".to_string(), "alice".to_string()],
+                vec!["2".to_string(), "bob".to_string()],
+            ],
+        );
+
+        assert!(iteration.bind_next(&mut context));
+        assert_eq!(context.get("row_id").map(String::as_str), Some("1"));
+        assert_eq!(context.get("row_user_name").map(String::as_str), Some("alice"));
+        assert!(iteration.bind_next(&mut context));
+        assert_eq!(context.get("row").map(String::as_str), Some("2"));
+        assert!(!iteration.bind_next(&mut context));
+    }
+}
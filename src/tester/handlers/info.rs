@@ -0,0 +1,51 @@
+//! Handlers for statement info output (--enable_info, --disable_info).
+//!
+//! When enabled, every statement is followed by `affected rows: N` and, if the
+//! server sent one, `info: <message>` (e.g. `Rows matched: 1  Changed: 1  Warnings: 0`).
+
+use crate::tester::command::Command;
+use crate::tester::database::ExecutionInfo;
+use crate::tester::tester::Tester;
+use anyhow::Result;
+use log::debug;
+
+pub fn enable_info(tester: &mut Tester, _cmd: &Command) -> Result<()> {
+    tester.display_info = true;
+    debug!("Info output enabled");
+    Ok(())
+}
+
+pub fn disable_info(tester: &mut Tester, _cmd: &Command) -> Result<()> {
+    tester.display_info = false;
+    debug!("Info output disabled");
+    Ok(())
+}
+
+/// Render the statement status in mysqltest format
+pub fn format_info(execution_info: &ExecutionInfo) -> String {
+    let mut output = format!("affected rows: {}\n", execution_info.affected_rows);
+    if !execution_info.info.is_empty() {
+        output.push_str(&format!("info: {}\n", execution_info.info));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_info() {
+        let update = ExecutionInfo {
+            affected_rows: 5,
+            last_insert_id: 0,
+            info: "Rows matched: 5  Changed: 5  Warnings: 0".to_string(),
+            warning_count: 0,
+        };
+        assert_eq!(
+            format_info(&update),
+            "affected rows: 5\ninfo: Rows matched: 5  Changed: 5  Warnings: 0\n"
+        );
+        assert_eq!(format_info(&ExecutionInfo::default()), "affected rows: 0\n");
+    }
+}
@@ -0,0 +1,208 @@
+//! Handlers for JSON aware results (--json_result, --enable_json_result, --disable_json_result).
+//!
+//! `--json_result [compact|pretty]` applies to the next statement: cells of
+//! JSON columns, and cells of other columns holding a JSON object or array,
+//! are written in canonical form (keys sorted, fixed whitespace) and compared
+//! structurally with the result file. `--enable_json_result [compact|pretty]`
+//! does the same for the JSON columns (by column type) of every following
+//! statement, until `--disable_json_result`.
+
+use crate::tester::command::Command;
+use crate::tester::database::ColumnMeta;
+use crate::tester::tester::Tester;
+use anyhow::{anyhow, Result};
+use log::debug;
+use mysql::consts::ColumnType;
+use serde_json::{Map, Value};
+
+/// How canonical JSON is written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum JsonFormat {
+    /// One line, no whitespace: `{"a":1,"b":[1,2]}`
+    #[default]
+    Compact,
+    /// Indented over several lines
+    Pretty,
+}
+
+impl JsonFormat {
+    fn parse(arg: &str) -> Result<Self> {
+        match arg.trim().to_ascii_lowercase().as_str() {
+            "" | "compact" => Ok(JsonFormat::Compact),
+            "pretty" => Ok(JsonFormat::Pretty),
+            other => Err(anyhow!("Invalid JSON result format '{}' (expected compact or pretty)", other)),
+        }
+    }
+}
+
+/// JSON result handling for one statement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonResult {
+    pub format: JsonFormat,
+    /// Also canonicalize JSON objects/arrays found in non-JSON columns
+    pub all_columns: bool,
+}
+
+pub fn execute(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let format = JsonFormat::parse(&cmd.args)?;
+    tester.pending_json_result = Some(JsonResult {
+        format,
+        all_columns: true,
+    });
+    debug!("JSON result ({:?}) enabled for next query", format);
+    Ok(())
+}
+
+pub fn enable_json_result(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let format = JsonFormat::parse(&cmd.args)?;
+    tester.json_result = Some(JsonResult {
+        format,
+        all_columns: false,
+    });
+    debug!("JSON result ({:?}) enabled for JSON columns", format);
+    Ok(())
+}
+
+pub fn disable_json_result(tester: &mut Tester, _cmd: &Command) -> Result<()> {
+    tester.json_result = None;
+    debug!("JSON result disabled");
+    Ok(())
+}
+
+/// Rewrite the JSON cells of a row in canonical form
+pub fn apply_to_row(row: &mut [String], columns: &[ColumnMeta], json: JsonResult) {
+    for (index, cell) in row.iter_mut().enumerate() {
+        let is_json_column = columns
+            .get(index)
+            .is_some_and(|column| column.column_type == ColumnType::MYSQL_TYPE_JSON);
+        if !is_json_column && !json.all_columns {
+            continue;
+        }
+        match parse(cell) {
+            Some(value) if is_json_column || value.is_object() || value.is_array() => {
+                *cell = canonical(&value, json.format);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parse a cell as JSON; None for NULL and anything that is not JSON.
+/// Numbers keep their text (serde_json `arbitrary_precision`), so big integers
+/// and long decimals are neither rounded nor compared as f64.
+pub fn parse(cell: &str) -> Option<Value> {
+    if cell == "NULL" {
+        return None;
+    }
+    serde_json::from_str(cell).ok()
+}
+
+/// `value` with object keys sorted, written in `format`
+pub fn canonical(value: &Value, format: JsonFormat) -> String {
+    let sorted = sort_keys(value);
+    match format {
+        JsonFormat::Compact => sorted.to_string(),
+        JsonFormat::Pretty => serde_json::to_string_pretty(&sorted).unwrap_or_else(|_| sorted.to_string()),
+    }
+}
+
+fn sort_keys(value: &Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let mut sorted = Map::new();
+            for (key, value) in entries {
+                sorted.insert(key.clone(), sort_keys(value));
+            }
+            Value::Object(sorted)
+        }
+        Value::Array(items) => Value::Array(items.iter().map(sort_keys).collect()),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, column_type: ColumnType) -> ColumnMeta {
+        ColumnMeta {
+            name: name.to_string(),
+            column_type,
+            schema: String::new(),
+            table: String::new(),
+            org_table: String::new(),
+            org_name: String::new(),
+            length: 0,
+            flags: mysql::consts::ColumnFlags::empty(),
+            decimals: 0,
+            charset: 63,
+        }
+    }
+
+    #[test]
+    fn test_canonical() {
+        let value = parse(r#"{"b": [1, {"d": 2, "c": 3}], "a": "x"}"#).unwrap();
+        assert_eq!(
+            canonical(&value, JsonFormat::Compact),
+            r#"{"a":"x","b":[1,{"c":3,"d":2}]}"#
+        );
+        assert_eq!(
+            canonical(&parse(r#"{"b": 1, "a": 2}"#).unwrap(), JsonFormat::Pretty),
+            "{\n  \"a\": 2,\n  \"b\": 1\n}"
+        );
+    }
+
+    #[test]
+    fn test_canonical_keeps_number_text() {
+        let value = parse(r#"{"big": 12345678901234567890123, "dec": 0.123456789012345678901234}"#).unwrap();
+        assert_eq!(
+            canonical(&value, JsonFormat::Compact),
+            r#"{"big":12345678901234567890123,"dec":0.123456789012345678901234}"#
+        );
+        // Values that are equal as f64 are still different numbers
+        assert_ne!(parse("[12345678901234567890123]"), parse("[12345678901234567890124]"));
+        assert_ne!(
+            parse("[0.123456789012345678901234]"),
+            parse("[0.123456789012345678901235]")
+        );
+        assert_eq!(parse("[12345678901234567890123]"), parse("[ 12345678901234567890123 ]"));
+    }
+
+    #[test]
+    fn test_apply_to_row() {
+        let columns = [
+            column("doc", ColumnType::MYSQL_TYPE_JSON),
+            column("text", ColumnType::MYSQL_TYPE_VAR_STRING),
+            column("n", ColumnType::MYSQL_TYPE_LONG),
+        ];
+        let row = vec![
+            r#"{"b": 1, "a": 2}"#.to_string(),
+            r#"{"y": 1, "x": 2}"#.to_string(),
+            "12".to_string(),
+        ];
+
+        // By column type only: the string column keeps its text
+        let mut by_type = row.clone();
+        let json = JsonResult { format: JsonFormat::Compact, all_columns: false };
+        apply_to_row(&mut by_type, &columns, json);
+        assert_eq!(by_type, [r#"{"a":2,"b":1}"#, r#"{"y": 1, "x": 2}"#, "12"]);
+
+        // --json_result: objects and arrays in any column, scalars untouched
+        let mut all = row.clone();
+        apply_to_row(&mut all, &columns, JsonResult { all_columns: true, ..json });
+        assert_eq!(all, [r#"{"a":2,"b":1}"#, r#"{"x":2,"y":1}"#, "12"]);
+
+        let mut null = vec!["NULL".to_string()];
+        apply_to_row(&mut null, &columns, json);
+        assert_eq!(null, ["NULL"]);
+    }
+
+    #[test]
+    fn test_format_parse() {
+        assert_eq!(JsonFormat::parse("").unwrap(), JsonFormat::Compact);
+        assert_eq!(JsonFormat::parse("PRETTY").unwrap(), JsonFormat::Pretty);
+        assert!(JsonFormat::parse("yaml").is_err());
+    }
+}
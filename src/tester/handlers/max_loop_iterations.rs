@@ -0,0 +1,42 @@
+//! Handler for the --max_loop_iterations command.
+//!
+//! Syntax: `--max_loop_iterations <N>`. Overrides the `--max-loop-iterations`
+//! CLI limit for the while loops that follow, until the end of the test.
+
+use crate::tester::command::Command;
+use crate::tester::tester::Tester;
+use anyhow::{anyhow, Result};
+use log::debug;
+
+pub fn execute(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    let limit = parse_limit(&expanded)?;
+
+    debug!("Max loop iterations set to {}", limit);
+    tester.max_loop_iterations = limit;
+    Ok(())
+}
+
+fn parse_limit(args: &str) -> Result<usize> {
+    let args = args.trim();
+    match args.parse::<usize>() {
+        Ok(limit) if limit > 0 => Ok(limit),
+        _ => Err(anyhow!(
+            "Invalid max_loop_iterations: expected a positive number. Got: {}",
+            args
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_limit() {
+        assert_eq!(parse_limit(" 500 ").unwrap(), 500);
+        assert!(parse_limit("0").is_err());
+        assert!(parse_limit("-1").is_err());
+        assert!(parse_limit("many").is_err());
+    }
+}
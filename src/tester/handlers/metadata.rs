@@ -0,0 +1,101 @@
+//! Handlers for result metadata output (--enable_metadata, --disable_metadata).
+//!
+//! When enabled, each result set is preceded by one line per column in
+//! mysqltest format (tab separated):
+//!
+//! ```text
+//! Catalog Database Table Table_alias Column Column_alias Type Length Max length Is_null Flags Decimals Charsetnr
+//! def test t1 t1 a a 3 11 1 Y 32768 0 63
+//! ```
+//!
+//! `Type` is the numeric protocol type, `Flags` the column flag bits and
+//! `Charsetnr` the collation id. `Max length` is the longest rendered value.
+
+use crate::tester::command::Command;
+use crate::tester::database::ColumnMeta;
+use crate::tester::tester::Tester;
+use anyhow::Result;
+use log::debug;
+use mysql::consts::ColumnFlags;
+
+const METADATA_HEADER: &str = "Catalog\tDatabase\tTable\tTable_alias\tColumn\tColumn_alias\tType\tLength\tMax length\tIs_null\tFlags\tDecimals\tCharsetnr";
+
+pub fn enable_metadata(tester: &mut Tester, _cmd: &Command) -> Result<()> {
+    tester.display_metadata = true;
+    debug!("Metadata output enabled");
+    Ok(())
+}
+
+pub fn disable_metadata(tester: &mut Tester, _cmd: &Command) -> Result<()> {
+    tester.display_metadata = false;
+    debug!("Metadata output disabled");
+    Ok(())
+}
+
+/// Render the column metadata of a result set; empty for statements without one
+pub fn format_metadata(columns: &[ColumnMeta], rows: &[Vec<String>]) -> String {
+    if columns.is_empty() {
+        return String::new();
+    }
+
+    let mut output = format!("{}\n", METADATA_HEADER);
+    for (idx, column) in columns.iter().enumerate() {
+        let max_length = rows
+            .iter()
+            .filter_map(|row| row.get(idx))
+            .map(|value| value.len())
+            .max()
+            .unwrap_or(0);
+        let is_null = if column.flags.contains(ColumnFlags::NOT_NULL_FLAG) {
+            "N"
+        } else {
+            "Y"
+        };
+
+        output.push_str(&format!(
+            "def\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            column.schema,
+            column.org_table,
+            column.table,
+            column.org_name,
+            column.name,
+            column.column_type as u8,
+            column.length,
+            max_length,
+            is_null,
+            column.flags.bits(),
+            column.decimals,
+            column.charset
+        ));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mysql::consts::ColumnType;
+
+    #[test]
+    fn test_format_metadata() {
+        let column = ColumnMeta {
+            name: "a".to_string(),
+            column_type: ColumnType::MYSQL_TYPE_LONG,
+            schema: "test".to_string(),
+            table: "t".to_string(),
+            org_table: "t1".to_string(),
+            org_name: "id".to_string(),
+            length: 11,
+            flags: ColumnFlags::NOT_NULL_FLAG | ColumnFlags::NUM_FLAG,
+            decimals: 0,
+            charset: 63,
+        };
+        let rows = vec![vec!["7".to_string()], vec!["1234".to_string()]];
+
+        assert_eq!(
+            format_metadata(&[column], &rows),
+            format!("{}\ndef\ttest\tt1\tt\tid\ta\t3\t11\t4\tN\t32769\t0\t63\n", METADATA_HEADER)
+        );
+        assert_eq!(format_metadata(&[], &[]), "");
+    }
+}
@@ -1,3 +1,6 @@
+pub mod character_set;
+pub mod column_names;
+pub mod compare;
 pub mod connect;
 pub mod connection;
 pub mod disconnect;
@@ -5,12 +8,26 @@ pub mod echo;
 pub mod error;
 pub mod eval;
 pub mod exec;
+pub mod foreach;
+pub mod info;
+pub mod json_result;
 pub mod let_handler;
+pub mod max_loop_iterations;
+pub mod metadata;
+pub mod ps_protocol;
 pub mod query_log;
+pub mod replace_column;
 pub mod replace_regex;
+pub mod replace_result;
+pub mod require;
 pub mod result_log;
+pub mod send_reap;
 pub mod sleep;
 pub mod sorted_result;
+pub mod timeout;
+pub mod value_format;
+pub mod vertical_results;
+pub mod warnings;
 
 // New enhanced syntax handlers
 pub mod var_operations;
@@ -0,0 +1,21 @@
+//! Handlers for prepared statement protocol mode (--enable_ps_protocol, --disable_ps_protocol).
+//!
+//! The mode starts from the `--ps-protocol` CLI flag and can be switched within
+//! a test. Statements that cannot be prepared still run with the text protocol.
+
+use crate::tester::command::Command;
+use crate::tester::tester::Tester;
+use anyhow::Result;
+use log::debug;
+
+pub fn enable_ps_protocol(tester: &mut Tester, _cmd: &Command) -> Result<()> {
+    tester.ps_protocol = true;
+    debug!("Prepared statement protocol enabled");
+    Ok(())
+}
+
+pub fn disable_ps_protocol(tester: &mut Tester, _cmd: &Command) -> Result<()> {
+    tester.ps_protocol = false;
+    debug!("Prepared statement protocol disabled");
+    Ok(())
+}
@@ -0,0 +1,87 @@
+//! Handler for the --replace_column command.
+//!
+//! Syntax: `--replace_column <col> <value> [<col> <value> ...]`, columns are
+//! 1-based. Values may be quoted to keep whitespace, e.g. `2 "<some time>"`.
+
+use crate::tester::command::Command;
+use crate::tester::tester::Tester;
+use crate::util::args::split_args;
+use anyhow::{anyhow, Result};
+use log::debug;
+
+pub fn execute(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    let rules = parse_replace_column(&expanded)?;
+
+    debug!("Replace column rules added: {:?}", rules);
+    tester.pending_replace_column.extend(rules);
+    Ok(())
+}
+
+/// Parse `col value` pairs into 0-based column indexes with their replacement.
+pub fn parse_replace_column(args: &str) -> Result<Vec<(usize, String)>> {
+    let tokens = split_args(args)?;
+
+    if tokens.is_empty() || tokens.len() % 2 != 0 {
+        return Err(anyhow!(
+            "Invalid replace_column: expected '<col> <value>' pairs. Got: {}",
+            args
+        ));
+    }
+
+    tokens
+        .chunks(2)
+        .map(|pair| {
+            let column: usize = pair[0].parse().map_err(|_| {
+                anyhow!(
+                    "Invalid replace_column: column '{}' is not a number",
+                    pair[0]
+                )
+            })?;
+            if column == 0 {
+                return Err(anyhow!(
+                    "Invalid replace_column: column numbers start at 1"
+                ));
+            }
+            Ok((column - 1, pair[1].clone()))
+        })
+        .collect()
+}
+
+/// Apply column replacements to one row in place.
+///
+/// Columns outside the row are ignored, matching mysqltest behaviour for
+/// statements that return fewer columns than the rule references.
+pub fn apply_to_row(row: &mut [String], rules: &[(usize, String)]) {
+    for (column, value) in rules {
+        if let Some(cell) = row.get_mut(*column) {
+            cell.clone_from(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pairs() {
+        let rules = parse_replace_column("1 # 3 '<TS>'").unwrap();
+        assert_eq!(rules, vec![(0, "#".to_string()), (2, "<TS>".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse_replace_column("").is_err());
+        assert!(parse_replace_column("1").is_err());
+        assert!(parse_replace_column("0 x").is_err());
+        assert!(parse_replace_column("a x").is_err());
+    }
+
+    #[test]
+    fn test_apply_to_row_ignores_missing_columns() {
+        let mut row = vec!["1".to_string(), "2024-01-01".to_string()];
+        apply_to_row(&mut row, &[(1, "<DATE>".to_string()), (5, "x".to_string())]);
+        assert_eq!(row, vec!["1", "<DATE>"]);
+    }
+}
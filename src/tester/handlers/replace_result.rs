@@ -0,0 +1,113 @@
+//! Handler for the --replace_result command.
+//!
+//! Syntax: `--replace_result <from> <to> [<from> <to> ...]`. Values may be
+//! quoted and may reference variables, e.g. `--replace_result $port PORT`.
+
+use crate::tester::command::Command;
+use crate::tester::tester::Tester;
+use crate::util::args::split_args;
+use anyhow::{anyhow, Result};
+use log::debug;
+
+pub fn execute(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    let pairs = parse_replace_result(&expanded)?;
+
+    debug!("Replace result pairs added: {:?}", pairs);
+    tester.pending_replace_result.extend(pairs);
+    Ok(())
+}
+
+/// Parse `from to` pairs. Empty `from` values are rejected since they would
+/// match everywhere.
+pub fn parse_replace_result(args: &str) -> Result<Vec<(String, String)>> {
+    let tokens = split_args(args)?;
+
+    if tokens.is_empty() || tokens.len() % 2 != 0 {
+        return Err(anyhow!(
+            "Invalid replace_result: expected '<from> <to>' pairs. Got: {}",
+            args
+        ));
+    }
+
+    tokens
+        .chunks(2)
+        .map(|pair| {
+            if pair[0].is_empty() {
+                return Err(anyhow!("Invalid replace_result: empty 'from' value"));
+            }
+            Ok((pair[0].clone(), pair[1].clone()))
+        })
+        .collect()
+}
+
+/// Replace all pairs in a single left-to-right pass.
+///
+/// Like mysqltest, replaced text is never matched again, so `a b b c` turns
+/// `ab` into `bc` rather than `cc`. When several `from` values match at the
+/// same position the first pair wins. Returns `None` if nothing matched.
+pub fn apply(input: &str, pairs: &[(String, String)]) -> Option<String> {
+    if pairs.is_empty() {
+        return None;
+    }
+
+    let mut output = String::with_capacity(input.len());
+    let mut changed = false;
+    let mut pos = 0;
+
+    while pos < input.len() {
+        let rest = &input[pos..];
+        if let Some((from, to)) = pairs.iter().find(|(from, _)| rest.starts_with(from.as_str())) {
+            output.push_str(to);
+            pos += from.len();
+            changed = true;
+        } else {
+            let ch = rest.chars().next().unwrap_or_default();
+            output.push(ch);
+            pos += ch.len_utf8();
+        }
+    }
+
+    changed.then_some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items
+            .iter()
+            .map(|(f, t)| (f.to_string(), t.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_pairs() {
+        let parsed = parse_replace_result("3306 PORT 'a b' \"\"").unwrap();
+        assert_eq!(parsed, pairs(&[("3306", "PORT"), ("a b", "")]));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse_replace_result("").is_err());
+        assert!(parse_replace_result("only_from").is_err());
+        assert!(parse_replace_result("'' x").is_err());
+    }
+
+    #[test]
+    fn test_apply_single_pass() {
+        let rules = pairs(&[("a", "b"), ("b", "c")]);
+        assert_eq!(apply("ab", &rules).as_deref(), Some("bc"));
+    }
+
+    #[test]
+    fn test_apply_no_match() {
+        let rules = pairs(&[("x", "y")]);
+        assert_eq!(apply("héllo", &rules), None);
+        assert_eq!(
+            apply("héllo x", &rules).as_deref(),
+            Some("héllo y")
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! Handlers for skip mechanics and capability probes.
+//!
+//! `--skip <reason>` and failed probes (`--require_engine`, `--require_version`,
+//! `--require_variable`, `--require <file>`) stop the test and mark it Skipped.
+//! `--exit` stops the test cleanly; the output produced so far is still checked.
+
+use crate::tester::command::Command;
+use crate::tester::tester::{StopRequest, Tester};
+use anyhow::{anyhow, Result};
+use log::debug;
+use std::cmp::Ordering;
+use std::path::PathBuf;
+
+pub fn skip(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    let reason = match expanded.trim() {
+        "" => "Skipped by --skip".to_string(),
+        reason => reason.to_string(),
+    };
+
+    debug!("Skip requested at line {}: {}", cmd.line, reason);
+    tester.stop_request = Some(StopRequest::Skip(reason));
+    Ok(())
+}
+
+pub fn exit(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    debug!("Exit requested at line {}", cmd.line);
+    tester.stop_request = Some(StopRequest::Exit);
+    Ok(())
+}
+
+pub fn require(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    let file = expanded.trim();
+    if file.is_empty() {
+        return Err(anyhow!("Invalid require: missing file name"));
+    }
+
+    debug!("Next query output must match require file: {}", file);
+    tester.pending_require = Some(PathBuf::from(file));
+    Ok(())
+}
+
+pub fn require_engine(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    let engine = expanded.trim();
+    if engine.is_empty() {
+        return Err(anyhow!("Invalid require_engine: missing engine name"));
+    }
+
+    let sql = format!(
+        "SELECT SUPPORT FROM information_schema.ENGINES WHERE ENGINE = '{}'",
+        escape_string(engine)
+    );
+    let result = tester
+        .connection_manager
+        .current_database()?
+        .query_with_metadata(&sql)?;
+    let supported = result
+        .rows
+        .first()
+        .and_then(|row| row.first())
+        .is_some_and(|support| {
+            support.eq_ignore_ascii_case("YES") || support.eq_ignore_ascii_case("DEFAULT")
+        });
+
+    if !supported {
+        skip_test(tester, format!("Test requires storage engine {}", engine));
+    }
+    Ok(())
+}
+
+pub fn require_version(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    let (op, required) = parse_version_requirement(&expanded)?;
+
+    let result = tester
+        .connection_manager
+        .current_database()?
+        .query_with_metadata("SELECT VERSION()")?;
+    let server_version = result
+        .rows
+        .first()
+        .and_then(|row| row.first())
+        .cloned()
+        .ok_or_else(|| anyhow!("require_version: SELECT VERSION() returned no rows"))?;
+    let actual = parse_version(&server_version).ok_or_else(|| {
+        anyhow!(
+            "require_version: cannot parse server version '{}'",
+            server_version
+        )
+    })?;
+
+    if !op.matches(compare_versions(&actual, &required)) {
+        skip_test(
+            tester,
+            format!(
+                "Test requires server version {} {}, found {}",
+                op.as_str(),
+                required
+                    .iter()
+                    .map(|part| part.to_string())
+                    .collect::<Vec<_>>()
+                    .join("."),
+                server_version
+            ),
+        );
+    }
+    Ok(())
+}
+
+pub fn require_variable(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    let (name, expected) = parse_variable_requirement(&expanded)?;
+
+    let sql = variable_query(&name);
+    let result = tester
+        .connection_manager
+        .current_database()?
+        .query_with_metadata(&sql)?;
+
+    match result.rows.first().and_then(|row| row.get(1)) {
+        Some(actual) if actual.eq_ignore_ascii_case(&expected) => {}
+        Some(actual) => skip_test(
+            tester,
+            format!(
+                "Test requires variable {}={}, found {}",
+                name, expected, actual
+            ),
+        ),
+        None => skip_test(
+            tester,
+            format!("Test requires variable {}, which is not defined", name),
+        ),
+    }
+    Ok(())
+}
+
+fn skip_test(tester: &mut Tester, reason: String) {
+    debug!("Capability probe failed: {}", reason);
+    tester.stop_request = Some(StopRequest::Skip(reason));
+}
+
+/// Exact lookup of a system variable (with LIKE, `_` in names is a wildcard)
+fn variable_query(name: &str) -> String {
+    format!(
+        "SHOW VARIABLES WHERE Variable_name = '{}'",
+        escape_string(name)
+    )
+}
+
+/// Escape a value for use inside a single-quoted SQL string literal
+fn escape_string(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+/// Comparison operator of a `--require_version` probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionOp {
+    Ge,
+    Gt,
+    Le,
+    Lt,
+    Eq,
+    Ne,
+}
+
+impl VersionOp {
+    // Two-character operators first so `>=` is not read as `>`
+    const ALL: [(&'static str, VersionOp); 6] = [
+        (">=", VersionOp::Ge),
+        ("<=", VersionOp::Le),
+        ("!=", VersionOp::Ne),
+        (">", VersionOp::Gt),
+        ("<", VersionOp::Lt),
+        ("=", VersionOp::Eq),
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|(_, op)| op == self)
+            .map(|(s, _)| *s)
+            .unwrap_or("=")
+    }
+
+    /// Whether `actual.cmp(required)` satisfies this operator
+    pub fn matches(&self, ordering: Ordering) -> bool {
+        match self {
+            VersionOp::Ge => ordering != Ordering::Less,
+            VersionOp::Gt => ordering == Ordering::Greater,
+            VersionOp::Le => ordering != Ordering::Greater,
+            VersionOp::Lt => ordering == Ordering::Less,
+            VersionOp::Eq => ordering == Ordering::Equal,
+            VersionOp::Ne => ordering != Ordering::Equal,
+        }
+    }
+}
+
+/// Parse `[op] X.Y.Z`; the operator defaults to `>=` when omitted.
+pub fn parse_version_requirement(args: &str) -> Result<(VersionOp, Vec<u64>)> {
+    let args = args.trim();
+    let (op, rest) = VersionOp::ALL
+        .iter()
+        .find_map(|(s, op)| args.strip_prefix(s).map(|rest| (*op, rest)))
+        .unwrap_or((VersionOp::Ge, args));
+
+    let version = parse_version(rest.trim())
+        .ok_or_else(|| anyhow!("Invalid require_version: expected '[op] X.Y.Z'. Got: {}", args))?;
+    Ok((op, version))
+}
+
+/// Parse the leading numeric part of a version string, e.g. `8.0.33-log` -> `[8, 0, 33]`
+pub fn parse_version(version: &str) -> Option<Vec<u64>> {
+    let numeric: String = version
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+
+    let parts: Vec<u64> = numeric
+        .split('.')
+        .filter(|part| !part.is_empty())
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts)
+    }
+}
+
+/// Compare versions component by component, missing components count as 0
+pub fn compare_versions(a: &[u64], b: &[u64]) -> Ordering {
+    let len = a.len().max(b.len());
+    (0..len)
+        .map(|i| {
+            let x = a.get(i).copied().unwrap_or(0);
+            let y = b.get(i).copied().unwrap_or(0);
+            x.cmp(&y)
+        })
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Parse `name=value` for `--require_variable`; the value may be quoted
+pub fn parse_variable_requirement(args: &str) -> Result<(String, String)> {
+    let (name, value) = args.split_once('=').ok_or_else(|| {
+        anyhow!(
+            "Invalid require_variable: expected 'name=value'. Got: {}",
+            args.trim()
+        )
+    })?;
+
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow!("Invalid require_variable: missing variable name"));
+    }
+    let value = value.trim();
+    let unquoted = ['\'', '"']
+        .iter()
+        .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)))
+        .unwrap_or(value);
+    Ok((name.to_string(), unquoted.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(parse_version("8.0.33"), Some(vec![8, 0, 33]));
+        assert_eq!(parse_version("8.0.33-0ubuntu0.22.04.2"), Some(vec![8, 0, 33]));
+        assert_eq!(parse_version("5.7.44-log"), Some(vec![5, 7, 44]));
+        assert_eq!(parse_version("unknown"), None);
+    }
+
+    #[test]
+    fn test_parse_version_requirement() {
+        assert_eq!(
+            parse_version_requirement(">= 8.0.20").unwrap(),
+            (VersionOp::Ge, vec![8, 0, 20])
+        );
+        assert_eq!(
+            parse_version_requirement("<8.0").unwrap(),
+            (VersionOp::Lt, vec![8, 0])
+        );
+        assert_eq!(
+            parse_version_requirement("8.0.20").unwrap(),
+            (VersionOp::Ge, vec![8, 0, 20])
+        );
+        assert!(parse_version_requirement(">= abc").is_err());
+    }
+
+    #[test]
+    fn test_version_comparison() {
+        let actual = parse_version("8.0.33").unwrap();
+        let check = |req: &str| {
+            let (op, required) = parse_version_requirement(req).unwrap();
+            op.matches(compare_versions(&actual, &required))
+        };
+
+        assert!(check(">= 8.0.20"));
+        assert!(check(">= 8.0"));
+        assert!(check("= 8.0.33"));
+        assert!(check("!= 5.7"));
+        assert!(!check("> 8.0.33"));
+        assert!(!check("< 8.0"));
+        assert!(check("<= 8.0.33.0"));
+    }
+
+    #[test]
+    fn test_parse_variable_requirement() {
+        assert_eq!(
+            parse_variable_requirement("log_bin=ON").unwrap(),
+            ("log_bin".to_string(), "ON".to_string())
+        );
+        assert_eq!(
+            parse_variable_requirement(" sql_mode = '' ").unwrap(),
+            ("sql_mode".to_string(), String::new())
+        );
+        assert!(parse_variable_requirement("log_bin").is_err());
+        assert!(parse_variable_requirement("=ON").is_err());
+    }
+
+    #[test]
+    fn test_variable_query() {
+        assert_eq!(
+            variable_query("log_bin"),
+            "SHOW VARIABLES WHERE Variable_name = 'log_bin'"
+        );
+        assert_eq!(
+            variable_query("a'b"),
+            "SHOW VARIABLES WHERE Variable_name = 'a\\'b'"
+        );
+    }
+}
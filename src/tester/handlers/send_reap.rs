@@ -0,0 +1,30 @@
+//! Handlers for the --send and --reap commands.
+//!
+//! `--send <sql>` starts a statement on the current connection without waiting
+//! for it; `--send` on its own sends the next SQL statement instead. `--reap`
+//! waits for the statement on the current connection and writes its result.
+
+use crate::tester::command::Command;
+use crate::tester::tester::Tester;
+use anyhow::{anyhow, Result};
+use log::debug;
+
+pub fn send(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    if cmd.args.trim().is_empty() {
+        tester.pending_send = true;
+        debug!("Next statement will be sent asynchronously");
+        return Ok(());
+    }
+    tester.send_sql_query(&cmd.args, cmd.line)
+}
+
+pub fn reap(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    if !cmd.args.trim().is_empty() {
+        return Err(anyhow!(
+            "--reap takes no arguments, got '{}' at line {}",
+            cmd.args.trim(),
+            cmd.line
+        ));
+    }
+    tester.reap_sql_query(cmd.line)
+}
@@ -0,0 +1,20 @@
+//! Handler for the --timeout command.
+//!
+//! Syntax: `--timeout <duration>` (e.g. `30s`, `500ms`, `2m`; `0` disables).
+//! Overrides the `--query-timeout` CLI limit for the statements that follow,
+//! until the end of the test.
+
+use crate::tester::command::Command;
+use crate::tester::tester::Tester;
+use crate::tester::watchdog::parse_duration;
+use anyhow::Result;
+use log::debug;
+
+pub fn execute(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    let timeout = parse_duration(&expanded)?;
+
+    debug!("Statement timeout set to {:?}", timeout);
+    tester.query_timeout = Some(timeout).filter(|timeout| !timeout.is_zero());
+    Ok(())
+}
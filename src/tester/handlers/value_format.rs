@@ -0,0 +1,21 @@
+//! Handler for the --value_format command.
+//!
+//! Syntax: `--value_format binary=hex null=<NULL>` (see `tester::value_format`).
+//! Applies to every connection until the end of the test; without options the
+//! default rendering is restored.
+
+use crate::tester::command::Command;
+use crate::tester::tester::Tester;
+use crate::tester::value_format::ValueFormat;
+use anyhow::Result;
+use log::debug;
+
+pub fn execute(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    let format = ValueFormat::parse(&expanded)?;
+
+    debug!("Value format set to {:?}", format);
+    tester.connection_manager.set_value_format(format.clone());
+    tester.value_format = format;
+    Ok(())
+}
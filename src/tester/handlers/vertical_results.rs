@@ -0,0 +1,86 @@
+//! Handlers for vertical result mode (--vertical_results, --horizontal_results).
+
+use crate::tester::command::Command;
+use crate::tester::tester::Tester;
+use anyhow::Result;
+use log::debug;
+
+const ROW_SEPARATOR_STARS: &str = "***************************";
+
+pub fn enable_vertical_results(tester: &mut Tester, _cmd: &Command) -> Result<()> {
+    tester.vertical_results = true;
+    debug!("Vertical results enabled");
+    Ok(())
+}
+
+pub fn disable_vertical_results(tester: &mut Tester, _cmd: &Command) -> Result<()> {
+    tester.vertical_results = false;
+    debug!("Vertical results disabled");
+    Ok(())
+}
+
+/// Render rows like the mysql client's `\G` output:
+///
+/// ```text
+/// *************************** 1. row ***************************
+///   id: 1
+/// name: foo
+/// ```
+///
+/// Column names are right-aligned to the widest name. Cells beyond the known
+/// columns fall back to their 1-based position as the name.
+pub fn format_vertical(columns: &[String], rows: &[Vec<String>]) -> String {
+    let name_of = |idx: usize| -> String {
+        columns
+            .get(idx)
+            .cloned()
+            .unwrap_or_else(|| (idx + 1).to_string())
+    };
+    let width = rows
+        .iter()
+        .flat_map(|row| (0..row.len()).map(|idx| name_of(idx).chars().count()))
+        .max()
+        .unwrap_or(0);
+
+    let mut output = String::new();
+    for (row_idx, row) in rows.iter().enumerate() {
+        output.push_str(&format!(
+            "{} {}. row {}\n",
+            ROW_SEPARATOR_STARS,
+            row_idx + 1,
+            ROW_SEPARATOR_STARS
+        ));
+        for (idx, value) in row.iter().enumerate() {
+            output.push_str(&format!("{:>width$}: {}\n", name_of(idx), value, width = width));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_vertical() {
+        let columns = vec!["id".to_string(), "name".to_string()];
+        let rows = vec![
+            vec!["1".to_string(), "foo".to_string()],
+            vec!["2".to_string(), "NULL".to_string()],
+        ];
+        let expected = "\
+*************************** 1. row ***************************
+  id: 1
+name: foo
+*************************** 2. row ***************************
+  id: 2
+name: NULL
+";
+        assert_eq!(format_vertical(&columns, &rows), expected);
+    }
+
+    #[test]
+    fn test_format_vertical_empty() {
+        assert_eq!(format_vertical(&["a".to_string()], &[]), "");
+    }
+}
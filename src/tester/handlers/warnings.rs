@@ -0,0 +1,154 @@
+//! Handlers for warning capture (--enable_warnings, --disable_warnings).
+//!
+//! When enabled, `SHOW WARNINGS` is run on the connection that executed each
+//! statement and the warnings are appended to its output in mysqltest format
+//! (columns are tab separated):
+//!
+//! ```text
+//! Warnings:
+//! Warning 1265 Data truncated for column 'c' at row 1
+//! ```
+//!
+//! `--disable_warnings ER_X[,...]` only suppresses the listed codes and
+//! `--enable_warnings ER_X[,...]` brings them back; without arguments the
+//! commands switch the whole mode on or off.
+
+use crate::tester::command::Command;
+use crate::tester::error_codes;
+use crate::tester::tester::Tester;
+use anyhow::{anyhow, Result};
+use log::debug;
+use std::collections::HashSet;
+
+/// Statement used to fetch the warnings of the previous statement
+pub const SHOW_WARNINGS: &str = "SHOW WARNINGS";
+
+/// Current warning capture settings
+#[derive(Debug, Clone, Default)]
+pub struct WarningSettings {
+    /// Whether warnings are captured at all (off by default)
+    pub enabled: bool,
+    /// Warning codes that are never printed
+    pub suppressed: HashSet<u16>,
+}
+
+impl WarningSettings {
+    /// Render `SHOW WARNINGS` rows (Level, Code, Message). Suppressed codes are
+    /// dropped; returns an empty string when nothing is left.
+    pub fn format(&self, rows: &[Vec<String>]) -> String {
+        let lines: Vec<String> = rows
+            .iter()
+            .filter(|row| {
+                row.get(1)
+                    .and_then(|code| code.parse::<u16>().ok())
+                    .is_none_or(|code| !self.suppressed.contains(&code))
+            })
+            .map(|row| row.join("\t"))
+            .collect();
+
+        if lines.is_empty() {
+            return String::new();
+        }
+        format!("Warnings:\n{}\n", lines.join("\n"))
+    }
+}
+
+/// Warning count of a result set followed by further ones, whose warnings
+/// cannot be listed (`SHOW WARNINGS` only reports the last statement)
+pub fn format_count(count: u16) -> String {
+    if count == 0 {
+        return String::new();
+    }
+    format!("warning count: {}\n", count)
+}
+
+pub fn enable_warnings(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    if expanded.trim().is_empty() {
+        tester.warnings.enabled = true;
+        tester.warnings.suppressed.clear();
+        debug!("Warnings enabled");
+    } else {
+        for code in parse_codes(&expanded)? {
+            tester.warnings.suppressed.remove(&code);
+        }
+        debug!("Warnings re-enabled: {}", expanded.trim());
+    }
+    Ok(())
+}
+
+pub fn disable_warnings(tester: &mut Tester, cmd: &Command) -> Result<()> {
+    let expanded = tester.variable_context.expand(&cmd.args)?;
+    if expanded.trim().is_empty() {
+        tester.warnings.enabled = false;
+        debug!("Warnings disabled");
+    } else {
+        tester.warnings.suppressed.extend(parse_codes(&expanded)?);
+        debug!("Warnings suppressed: {}", expanded.trim());
+    }
+    Ok(())
+}
+
+/// Parse a comma separated list of warning codes or `ER_*`/`WARN_*` names
+fn parse_codes(args: &str) -> Result<Vec<u16>> {
+    args.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            item.parse::<u16>()
+                .ok()
+                .or_else(|| error_codes::error_code(item))
+                .ok_or_else(|| anyhow!("Unknown warning code or name: {}", item))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warning(level: &str, code: &str, message: &str) -> Vec<String> {
+        vec![level.to_string(), code.to_string(), message.to_string()]
+    }
+
+    #[test]
+    fn test_parse_codes() {
+        assert_eq!(
+            parse_codes("1265, ER_WARN_DEPRECATED_SYNTAX").unwrap(),
+            vec![1265, 1287]
+        );
+        assert_eq!(parse_codes("WARN_DATA_TRUNCATED").unwrap(), vec![1265]);
+        assert!(parse_codes("ER_NOT_A_REAL_WARNING").is_err());
+    }
+
+    #[test]
+    fn test_format_warnings() {
+        let rows = vec![
+            warning("Warning", "1265", "Data truncated for column 'c' at row 1"),
+            warning("Note", "1051", "Unknown table 'test.t9'"),
+        ];
+        let mut settings = WarningSettings {
+            enabled: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            settings.format(&rows),
+            "Warnings:\nWarning\t1265\tData truncated for column 'c' at row 1\nNote\t1051\tUnknown table 'test.t9'\n"
+        );
+
+        settings.suppressed.insert(1265);
+        assert_eq!(
+            settings.format(&rows),
+            "Warnings:\nNote\t1051\tUnknown table 'test.t9'\n"
+        );
+
+        settings.suppressed.insert(1051);
+        assert_eq!(settings.format(&rows), "");
+    }
+
+    #[test]
+    fn test_format_count() {
+        assert_eq!(format_count(0), "");
+        assert_eq!(format_count(2), "warning count: 2\n");
+    }
+}
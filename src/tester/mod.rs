@@ -1,13 +1,20 @@
 pub mod command;
+pub mod compare;
 pub mod conn;
 pub mod connection_manager;
 pub mod database;
+pub mod error_codes;
 pub mod error_handler;
 pub mod expression;
 pub mod handlers;
 pub mod parser;
 pub mod pest_parser;
+pub mod protocol;
 pub mod query;
 pub mod registry;
+pub mod result_pattern;
+pub mod review;
 pub mod tester;
+pub mod value_format;
 pub mod variables;
+pub mod watchdog;
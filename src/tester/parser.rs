@@ -44,6 +44,9 @@ pub static COMMAND_MAP: phf::Map<&'static str, QueryType> = phf_map! {
     "let" => QueryType::Let,
     "eval" => QueryType::Eval,
     "require" => QueryType::Require,
+    "require_engine" => QueryType::RequireEngine,
+    "require_version" => QueryType::RequireVersion,
+    "require_variable" => QueryType::RequireVariable,
     "source" => QueryType::Source,
     "comment" => QueryType::Comment,
     "connect" => QueryType::Connect,
@@ -54,9 +57,27 @@ pub static COMMAND_MAP: phf::Map<&'static str, QueryType> = phf_map! {
     "enable_query_log" => QueryType::EnableQueryLog,
     "disable_result_log" => QueryType::DisableResultLog,
     "enable_result_log" => QueryType::EnableResultLog,
+    "disable_column_names" => QueryType::DisableColumnNames,
+    "enable_column_names" => QueryType::EnableColumnNames,
+    "enable_warnings" => QueryType::EnableWarnings,
+    "disable_warnings" => QueryType::DisableWarnings,
+    "enable_info" => QueryType::EnableInfo,
+    "disable_info" => QueryType::DisableInfo,
+    "enable_metadata" => QueryType::EnableMetadata,
+    "disable_metadata" => QueryType::DisableMetadata,
+    "enable_ps_protocol" => QueryType::EnablePsProtocol,
+    "disable_ps_protocol" => QueryType::DisablePsProtocol,
     "sorted_result" => QueryType::SortedResult,
     "enable_sort_result" => QueryType::EnableSortResult,
     "disable_sort_result" => QueryType::DisableSortResult,
+    "compare" => QueryType::Compare,
+    "enable_compare" => QueryType::EnableCompare,
+    "disable_compare" => QueryType::DisableCompare,
+    "json_result" => QueryType::JsonResult,
+    "enable_json_result" => QueryType::EnableJsonResult,
+    "disable_json_result" => QueryType::DisableJsonResult,
+    "value_format" => QueryType::ValueFormat,
+    "character_set" => QueryType::CharacterSet,
     "change_user" => QueryType::ChangeUser,
     "eof" => QueryType::EndOfFile,
     "begin_concurrent" => QueryType::BeginConcurrent,
@@ -64,8 +85,10 @@ pub static COMMAND_MAP: phf::Map<&'static str, QueryType> = phf_map! {
     "concurrent" => QueryType::Concurrent,
     "vertical_results" => QueryType::VerticalResults,
     "horizontal_results" => QueryType::HorizontalResults,
+    "query_vertical" => QueryType::QueryVertical,
     "send" => QueryType::Send,
     "recv" => QueryType::Recv,
+    "reap" => QueryType::Recv,
     "wait" => QueryType::Wait,
     "real_sleep" => QueryType::RealSleep,
     "query_async" => QueryType::QueryAsync,
@@ -78,8 +101,15 @@ pub static COMMAND_MAP: phf::Map<&'static str, QueryType> = phf_map! {
     "exit" => QueryType::Exit,
     // Control flow commands
     "if" => QueryType::If,
+    "elseif" => QueryType::ElseIf,
+    "else" => QueryType::Else,
     "while" => QueryType::While,
+    "foreach" => QueryType::Foreach,
     "end" => QueryType::End,
+    "break" => QueryType::Break,
+    "continue" => QueryType::Continue,
+    "max_loop_iterations" => QueryType::MaxLoopIterations,
+    "timeout" => QueryType::Timeout,
 };
 
 
@@ -152,4 +182,20 @@ mod tests {
         assert_eq!(queries[3].query_type, QueryType::Query);
         assert_eq!(queries[3].query, "SELECT 2");
     }
+
+    #[test]
+    fn test_parse_compare_commands() {
+        let mut parser = default_parser();
+        let content = "--compare float_epsilon=1e-9 case_insensitive
+--enable_compare ignore_trailing_ws
+--disable_compare";
+        let queries = parser.parse(content).expect("Failed to parse compare commands");
+
+        assert_eq!(queries.len(), 3);
+        assert_eq!(queries[0].query_type, QueryType::Compare);
+        assert_eq!(queries[0].query, "float_epsilon=1e-9 case_insensitive");
+        assert_eq!(queries[1].query_type, QueryType::EnableCompare);
+        assert_eq!(queries[1].query, "ignore_trailing_ws");
+        assert_eq!(queries[2].query_type, QueryType::DisableCompare);
+    }
 }
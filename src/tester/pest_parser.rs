@@ -31,33 +31,35 @@ impl PestParser {
         Self::default()
     }
 
-    /// Convert pest parse tree to Query objects (memory pool optimized)
+    /// The statements of the parse tree, with the test_file and line wrappers removed
+    fn statement_pairs(pairs: pest::iterators::Pairs<Rule>) -> Vec<pest::iterators::Pair<Rule>> {
+        let mut statements = Vec::new();
+        for pair in pairs {
+            match pair.as_rule() {
+                Rule::test_file | Rule::line => statements.extend(Self::statement_pairs(pair.into_inner())),
+                _ => statements.push(pair),
+            }
+        }
+        statements
+    }
+
+    /// Convert pest parse tree to Query objects (memory pool optimized).
+    /// SQL lines are collected across lines until the delimiter ends the statement.
     fn convert_to_queries(&mut self, pairs: pest::iterators::Pairs<Rule>) -> Result<Vec<Query>> {
         let mut queries = Vec::new();
         let mut pending_sql_lines = get_string_vec();  // Use memory pool for SQL lines
-        let mut line_num = 1;
+        let mut pending_sql_line = 1;  // Line where the pending statement starts
 
-        for pair in pairs {
-            // Update line number based on the pair's position
-            let pair_line = pair.line_col().0;
-            line_num = pair_line;
+        for pair in Self::statement_pairs(pairs) {
+            // Line number based on the pair's position
+            let line_num = pair.line_col().0;
             
             
             match pair.as_rule() {
-                Rule::test_file => {
-                    // Recursively process the test file contents
-                    let inner_queries = self.convert_to_queries(pair.into_inner())?;
-                    queries.extend(inner_queries);
-                }
-                Rule::line => {
-                    // Recursively process the line contents
-                    let inner_queries = self.convert_to_queries(pair.into_inner())?;
-                    queries.extend(inner_queries);
-                }
                 Rule::comment => {
                     // Finalize any pending SQL before processing comment
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
                     let comment_text = self.extract_comment_text(pair)?;
@@ -71,10 +73,14 @@ impl PestParser {
                 Rule::command => {
                     // Finalize any pending SQL before processing command
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
                     let (query_type, query_content) = self.parse_command_pair(pair)?;
+                    // `--delimiter` is matched as a command; later SQL ends at the new delimiter
+                    if query_type == QueryType::Delimiter {
+                        self.delimiter = query_content.trim().to_string();
+                    }
                     queries.push(Query {
                         query_type,
                         query: query_content,
@@ -85,7 +91,7 @@ impl PestParser {
                 Rule::delimiter_change => {
                     // Finalize any pending SQL before changing delimiter
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
                     let delimiter_value = self.extract_delimiter_value(pair)?;
@@ -100,7 +106,7 @@ impl PestParser {
                 Rule::if_stmt => {
                     // Finalize any pending SQL before processing control flow
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
                     let condition = self.extract_condition(pair)?;
@@ -114,7 +120,7 @@ impl PestParser {
                 Rule::while_stmt => {
                     // Finalize any pending SQL before processing control flow
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
                     let condition = self.extract_condition(pair)?;
@@ -125,14 +131,65 @@ impl PestParser {
                         options: QueryOptions::default(),
                     });
                 }
-                Rule::end_stmt => {
-                    // Finalize any pending SQL before processing end
+                Rule::foreach_stmt => {
+                    // Finalize any pending SQL before processing control flow
+                    if !pending_sql_lines.is_empty() {
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
+                    }
+
+                    let spec = pair
+                        .into_inner()
+                        .find(|inner| inner.as_rule() == Rule::foreach_spec)
+                        .map(|inner| inner.as_str().trim().to_string())
+                        .ok_or_else(|| anyhow!("Failed to extract foreach header"))?;
+                    queries.push(Query {
+                        query_type: QueryType::Foreach,
+                        query: spec,
+                        line: line_num,
+                        options: QueryOptions::default(),
+                    });
+                }
+                Rule::elseif_stmt => {
+                    // Finalize any pending SQL before processing control flow
+                    if !pending_sql_lines.is_empty() {
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
+                    }
+
+                    let condition = self.extract_condition(pair)?;
+                    queries.push(Query {
+                        query_type: QueryType::ElseIf,
+                        query: condition,
+                        line: line_num,
+                        options: QueryOptions::default(),
+                    });
+                }
+                Rule::else_stmt
+                | Rule::end_stmt
+                | Rule::close_brace_stmt
+                | Rule::break_stmt
+                | Rule::continue_stmt => {
+                    // A bare `else` or `}` line inside a statement that has not reached
+                    // its delimiter yet (e.g. a stored procedure body) is SQL text
+                    let text = pair.as_str().trim();
+                    if !pending_sql_lines.is_empty() && (text == "else" || text == "}") {
+                        self.process_sql_line(&mut pending_sql_lines, &mut queries, text.to_string(), pending_sql_line)?;
+                        continue;
+                    }
+
+                    // Finalize any pending SQL before processing else/end
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
+                    let query_type = match pair.as_rule() {
+                        Rule::else_stmt => QueryType::Else,
+                        Rule::end_stmt => QueryType::End,
+                        Rule::break_stmt => QueryType::Break,
+                        Rule::continue_stmt => QueryType::Continue,
+                        _ => QueryType::CloseBrace,
+                    };
                     queries.push(Query {
-                        query_type: QueryType::End,
+                        query_type,
                         query: String::new(),
                         line: line_num,
                         options: QueryOptions::default(),
@@ -146,25 +203,28 @@ impl PestParser {
                     if let Some(query) = self.try_parse_new_syntax(&sql_content, line_num)? {
                         // Finalize any pending SQL first
                         if !pending_sql_lines.is_empty() {
-                            self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                            self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                         }
                         log::debug!("Processed as new syntax query at line {}", line_num);
                         queries.push(query);
                     } else {
                         // Process as regular SQL
                         log::debug!("Processing as regular SQL at line {}: '{}'", line_num, sql_content);
+                        if pending_sql_lines.is_empty() {
+                            pending_sql_line = line_num;
+                        }
                         self.process_sql_line(
                             &mut pending_sql_lines,
                             &mut queries,
                             sql_content,
-                            line_num,
+                            pending_sql_line,
                         )?;
                     }
                 }
                 Rule::let_stmt => {
                     // Finalize any pending SQL before processing let
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
                     let let_args = self.extract_let_args(pair)?;
@@ -178,7 +238,7 @@ impl PestParser {
                 Rule::inc_stmt => {
                     // Finalize any pending SQL before processing inc statement
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
                     // Extract variable name from "inc $varname"
@@ -194,7 +254,7 @@ impl PestParser {
                 Rule::inc_operation => {
                     // Finalize any pending SQL before processing inc operation
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
                     // Extract variable name from "inc $varname"
@@ -210,7 +270,7 @@ impl PestParser {
                 Rule::var_stmt => {
                     // Finalize any pending SQL before processing variable statement
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
                     // Process the inner var_operation
@@ -229,7 +289,7 @@ impl PestParser {
                 Rule::var_operation => {
                     // Finalize any pending SQL before processing variable operation
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
                     let (query_type, query_content) = self.parse_var_operation(pair)?;
@@ -243,7 +303,7 @@ impl PestParser {
                 Rule::batch_operation => {
                     // Finalize any pending SQL before processing batch operation
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
                     let (query_type, query_content) = self.parse_batch_operation(pair)?;
@@ -257,7 +317,7 @@ impl PestParser {
                 Rule::transaction_operation => {
                     // Finalize any pending SQL before processing transaction operation
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
                     let (query_type, query_content) = self.parse_transaction_operation(pair)?;
@@ -271,7 +331,7 @@ impl PestParser {
                 Rule::simple_command => {
                     // Finalize any pending SQL before processing simple command
                     if !pending_sql_lines.is_empty() {
-                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+                        self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
                     }
 
                     let (query_type, query_content) = self.parse_simple_command(pair)?;
@@ -293,7 +353,7 @@ impl PestParser {
 
         // Finalize any remaining SQL
         if !pending_sql_lines.is_empty() {
-            self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, line_num)?;
+            self.finalize_pending_sql(&mut queries, &mut pending_sql_lines, pending_sql_line)?;
         }
 
         Ok(queries)
@@ -351,6 +411,11 @@ impl PestParser {
             return Ok(());
         }
 
+        // With another delimiter, semicolons belong to the statement (procedure bodies)
+        if self.delimiter != ";" {
+            return self.finalize_pending_sql(queries, pending_sql_lines, line_num);
+        }
+
         // Check if this contains multiple complete SQL statements
         // Look for pattern: semicolon + newline + capital letter (start of new SQL)
         let lines: Vec<&str> = full_sql.lines().collect();
@@ -597,6 +662,14 @@ impl PestParser {
                 line: line_num,
                 options: QueryOptions::default(),
             }));
+        } else if content.starts_with("query_vertical ") {
+            let sql = content.strip_prefix("query_vertical ").unwrap_or("");
+            return Ok(Some(Query {
+                query_type: QueryType::QueryVertical,
+                query: self.remove_delimiter(sql),
+                line: line_num,
+                options: QueryOptions::default(),
+            }));
         } else if content.starts_with("source ") {
             let source_content = content.strip_prefix("source ").unwrap_or("");
             return Ok(Some(Query {
@@ -647,6 +720,93 @@ impl PestParser {
         COMMAND_MAP.get(command).copied().unwrap_or(QueryType::Unknown)
     }
 
+    /// Check that if/while blocks and their else/elseif branches are balanced,
+    /// and that break/continue only appear inside a while loop
+    fn validate_control_flow(&self, queries: &[Query]) -> Result<()> {
+        // (block type, line, else already seen)
+        let mut stack: Vec<(QueryType, usize, bool)> = Vec::new();
+
+        for query in queries {
+            match query.query_type {
+                QueryType::If | QueryType::While | QueryType::Foreach => {
+                    stack.push((query.query_type, query.line, false))
+                }
+                QueryType::ElseIf | QueryType::Else => {
+                    let name = if query.query_type == QueryType::Else {
+                        "else"
+                    } else {
+                        "elseif"
+                    };
+                    match stack.last_mut() {
+                        Some((QueryType::If, _, seen_else)) => {
+                            if *seen_else {
+                                return Err(anyhow!(
+                                    "'{}' after 'else' at line {}",
+                                    name,
+                                    query.line
+                                ));
+                            }
+                            *seen_else = query.query_type == QueryType::Else;
+                        }
+                        _ => {
+                            return Err(anyhow!(
+                                "'{}' without matching 'if' at line {}",
+                                name,
+                                query.line
+                            ))
+                        }
+                    }
+                }
+                QueryType::Break | QueryType::Continue
+                    if !stack.iter().any(|(block_type, _, _)| {
+                        matches!(block_type, QueryType::While | QueryType::Foreach)
+                    }) =>
+                {
+                    let name = if query.query_type == QueryType::Break {
+                        "break"
+                    } else {
+                        "continue"
+                    };
+                    return Err(anyhow!(
+                        "'{}' outside of a 'while' or 'foreach' loop at line {}",
+                        name,
+                        query.line
+                    ));
+                }
+                QueryType::End | QueryType::CloseBrace => {
+                    let Some(_) = stack.pop() else {
+                        let name = if query.query_type == QueryType::End {
+                            "end"
+                        } else {
+                            "}"
+                        };
+                        return Err(anyhow!(
+                            "'{}' without matching 'if', 'while' or 'foreach' at line {}",
+                            name,
+                            query.line
+                        ));
+                    };
+                }
+                _ => {}
+            }
+        }
+
+        if let Some((block_type, line, _)) = stack.last() {
+            let name = match block_type {
+                QueryType::If => "if",
+                QueryType::Foreach => "foreach",
+                _ => "while",
+            };
+            return Err(anyhow!(
+                "Unclosed '{}' block starting at line {}",
+                name,
+                line
+            ));
+        }
+
+        Ok(())
+    }
+
     fn extract_delimiter_value(&self, pair: pest::iterators::Pair<Rule>) -> Result<String> {
         for inner_pair in pair.into_inner() {
             if inner_pair.as_rule() == Rule::delimiter_value {
@@ -685,7 +845,6 @@ impl PestParser {
         Ok(pair_str.trim().to_string())
     }
 
-    #[allow(dead_code)]
     fn remove_delimiter(&self, sql: &str) -> String {
         let trimmed = sql.trim();
         if trimmed.ends_with(&self.delimiter) {
@@ -776,7 +935,9 @@ impl QueryParser for PestParser {
         let pairs = PestMySQLParser::parse(Rule::test_file, content)
             .map_err(|e| anyhow!("Pest parsing error: {}", e))?;
 
-        self.convert_to_queries(pairs)
+        let queries = self.convert_to_queries(pairs)?;
+        self.validate_control_flow(&queries)?;
+        Ok(queries)
     }
 }
 
@@ -807,6 +968,40 @@ mod tests {
         assert_eq!(queries[0].query, "hello world");
     }
 
+    #[test]
+    fn test_pest_parse_query_vertical() {
+        let mut parser = create_parser("pest").expect("Failed to create pest parser");
+        let content = "--query_vertical SELECT 1 AS a\nquery_vertical SELECT 2 AS b;";
+        let queries = parser.parse(content).expect("Failed to parse query_vertical");
+
+        assert_eq!(queries.len(), 2);
+        assert_eq!(queries[0].query_type, QueryType::QueryVertical);
+        assert_eq!(queries[0].query, "SELECT 1 AS a");
+        assert_eq!(queries[1].query_type, QueryType::QueryVertical);
+        assert_eq!(queries[1].query, "SELECT 2 AS b");
+    }
+
+    #[test]
+    fn test_pest_parse_send_reap() {
+        let mut parser = create_parser("pest").expect("Failed to create pest parser");
+        let content = "--send UPDATE t1 SET v = 2\n--send\nSELECT 1;\n--reap\n--reap";
+        let queries = parser.parse(content).expect("Failed to parse send/reap");
+
+        let types: Vec<QueryType> = queries.iter().map(|q| q.query_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                QueryType::Send,
+                QueryType::Send,
+                QueryType::Query,
+                QueryType::Recv,
+                QueryType::Recv
+            ]
+        );
+        assert_eq!(queries[0].query, "UPDATE t1 SET v = 2");
+        assert!(queries[1].query.is_empty());
+    }
+
     #[test]
     fn test_pest_parse_comment() {
         let mut parser = create_parser("pest").expect("Failed to create pest parser");
@@ -854,6 +1049,169 @@ mod tests {
         // The query may still contain the delimiter when parsed
         assert!(sql_query.unwrap().query.contains("SELECT 1"));
     }
+
+    fn control_flow_types(content: &str) -> Vec<QueryType> {
+        let mut parser = create_parser("pest").expect("Failed to create pest parser");
+        parser
+            .parse(content)
+            .expect("Failed to parse control flow")
+            .into_iter()
+            .map(|q| q.query_type)
+            .collect()
+    }
+
+    #[test]
+    fn test_pest_parse_else_branches() {
+        let content = "if ($a)\n--echo a\nelseif ($b)\n--echo b\nelse if ($c)\n--echo c\nelse\n--echo d\nend\n";
+        assert_eq!(
+            control_flow_types(content),
+            vec![
+                QueryType::If,
+                QueryType::Echo,
+                QueryType::ElseIf,
+                QueryType::Echo,
+                QueryType::ElseIf,
+                QueryType::Echo,
+                QueryType::Else,
+                QueryType::Echo,
+                QueryType::End,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pest_parse_brace_else() {
+        let mut parser = create_parser("pest").expect("Failed to create pest parser");
+        let content = "if ($a) {\nSELECT 1;\n} elseif ($b) {\nSELECT 2;\n} else {\nSELECT 3;\n}\n";
+        let queries = parser.parse(content).expect("Failed to parse brace syntax");
+
+        let types: Vec<QueryType> = queries.iter().map(|q| q.query_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                QueryType::If,
+                QueryType::Query,
+                QueryType::ElseIf,
+                QueryType::Query,
+                QueryType::Else,
+                QueryType::Query,
+                QueryType::CloseBrace,
+            ]
+        );
+        assert_eq!(queries[2].query.trim(), "$b");
+        assert_eq!(queries[3].query, "SELECT 2");
+    }
+
+    #[test]
+    fn test_pest_parse_unbalanced_branches() {
+        let mut parser = create_parser("pest").expect("Failed to create pest parser");
+
+        let err = parser.parse("--echo x\nelse\n--echo y\nend\n").unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+
+        let err = parser
+            .parse("while ($a) {\n--echo x\n} else {\n--echo y\n}\n")
+            .unwrap_err();
+        assert!(err.to_string().contains("without matching 'if' at line 3"), "{}", err);
+
+        let err = parser.parse("if ($a)\nelse\nelseif ($b)\nend\n").unwrap_err();
+        assert!(err.to_string().contains("'elseif' after 'else' at line 3"), "{}", err);
+
+        let err = parser.parse("if ($a) {\n--echo x\n").unwrap_err();
+        assert!(err.to_string().contains("starting at line 1"), "{}", err);
+    }
+
+    #[test]
+    fn test_pest_parse_procedure_body_in_block() {
+        let mut parser = create_parser("pest").expect("Failed to create pest parser");
+        let content = "--delimiter //\n\
+                       if ($a) {\n\
+                       CREATE PROCEDURE p(x INT)\n\
+                       BEGIN\n\
+                       IF x > 0 THEN\n\
+                       SELECT 1;\n\
+                       ELSE\n\
+                       SELECT 2;\n\
+                       END IF;\n\
+                       IF x > 1 THEN\n\
+                       SET @doc = '{\n\
+                       \"a\": 1\n\
+                       }\n\
+                       ';\n\
+                       else\n\
+                       SELECT 3;\n\
+                       END IF;\n\
+                       END//\n\
+                       }\n\
+                       --delimiter ;\n";
+        let queries = parser.parse(content).expect("Failed to parse procedure body");
+
+        // Bare `else` and `}` lines before the delimiter belong to the statement
+        let types: Vec<QueryType> = queries.iter().map(|q| q.query_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                QueryType::Delimiter,
+                QueryType::If,
+                QueryType::Query,
+                QueryType::CloseBrace,
+                QueryType::Delimiter,
+            ]
+        );
+        assert_eq!(queries[2].line, 3);
+        assert!(queries[2].query.starts_with("CREATE PROCEDURE p(x INT)"));
+        assert!(queries[2].query.contains("\"a\": 1\n}\n';\nelse\nSELECT 3;"), "{}", queries[2].query);
+        assert!(queries[2].query.ends_with("END"), "{}", queries[2].query);
+    }
+
+    #[test]
+    fn test_pest_parse_foreach() {
+        let mut parser = create_parser("pest").expect("Failed to create pest parser");
+        let content = "foreach $row in (SELECT id, COUNT(*) FROM t GROUP BY id) {\n--echo $row_id\n}\nforeach $x in (a, b)\n--echo $x\nend\n";
+        let queries = parser.parse(content).expect("Failed to parse foreach");
+
+        let types: Vec<QueryType> = queries.iter().map(|q| q.query_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                QueryType::Foreach,
+                QueryType::Echo,
+                QueryType::CloseBrace,
+                QueryType::Foreach,
+                QueryType::Echo,
+                QueryType::End,
+            ]
+        );
+        assert_eq!(
+            queries[0].query,
+            "$row in (SELECT id, COUNT(*) FROM t GROUP BY id) {"
+        );
+        assert_eq!(queries[3].query, "$x in (a, b)");
+    }
+
+    #[test]
+    fn test_pest_parse_break_continue() {
+        let content = "while ($i) {\nif ($a) {\nbreak\n}\n--continue\n}\n";
+        assert_eq!(
+            control_flow_types(content),
+            vec![
+                QueryType::While,
+                QueryType::If,
+                QueryType::Break,
+                QueryType::CloseBrace,
+                QueryType::Continue,
+                QueryType::CloseBrace,
+            ]
+        );
+
+        let mut parser = create_parser("pest").expect("Failed to create pest parser");
+        let err = parser.parse("if ($a)\nbreak\nend\n").unwrap_err();
+        assert!(
+            err.to_string().contains("'break' outside of a 'while' or 'foreach' loop at line 2"),
+            "{}",
+            err
+        );
+    }
 }
 
 // No re-export needed since we use the factory pattern
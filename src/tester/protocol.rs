@@ -0,0 +1,126 @@
+//! View / stored procedure protocol modes
+//!
+//! Like mysqltest's `--view-protocol` and `--sp-protocol`, each SELECT is run
+//! through a wrapper object instead of directly, and its output is compared
+//! with the same result file:
+//!
+//! - view: `CREATE VIEW mysqltest_tmp_v AS <select>` + `SELECT * FROM mysqltest_tmp_v`
+//! - sp: `CREATE PROCEDURE mysqltest_tmp_sp() <select>` + `CALL mysqltest_tmp_sp()`
+//!
+//! mysqltest's `--cursor-protocol` has no counterpart: the driver has no
+//! `COM_STMT_FETCH` support, so no server-side cursor can be opened.
+//!
+//! Views and procedures are created on a helper connection so the test
+//! connection never sees their implicit commit.
+
+use crate::cli::Args;
+
+const VIEW_NAME: &str = "mysqltest_tmp_v";
+const PROCEDURE_NAME: &str = "mysqltest_tmp_sp";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapProtocol {
+    View,
+    StoredProcedure,
+}
+
+/// A statement rewritten for one of the wrapping protocols
+#[derive(Debug, Clone, PartialEq)]
+pub struct WrappedQuery {
+    pub protocol: WrapProtocol,
+    /// Statements creating the wrapper object
+    pub setup: Vec<String>,
+    /// Statement run on the test connection instead of the original one
+    pub query: String,
+    /// Statements dropping the wrapper object again
+    pub teardown: Vec<String>,
+}
+
+impl WrapProtocol {
+    /// Protocol selected on the command line, if any
+    pub fn from_args(args: &Args) -> Option<Self> {
+        if args.view_protocol {
+            Some(WrapProtocol::View)
+        } else if args.sp_protocol {
+            Some(WrapProtocol::StoredProcedure)
+        } else {
+            None
+        }
+    }
+
+    /// Rewrite `sql` for this protocol. `schema` is the current database of
+    /// the test connection, where the wrapper objects are created.
+    pub fn wrap(self, sql: &str, schema: &str) -> WrappedQuery {
+        let sql = sql.trim().trim_end_matches(';');
+        let schema = format!("`{}`", schema.replace('`', "``"));
+        let (setup, query, teardown) = match self {
+            WrapProtocol::View => (
+                vec![
+                    format!("USE {}", schema),
+                    format!("CREATE OR REPLACE VIEW {} AS {}", VIEW_NAME, sql),
+                ],
+                format!("SELECT * FROM {}.{}", schema, VIEW_NAME),
+                vec![format!("DROP VIEW IF EXISTS {}.{}", schema, VIEW_NAME)],
+            ),
+            WrapProtocol::StoredProcedure => (
+                vec![
+                    format!("USE {}", schema),
+                    format!("DROP PROCEDURE IF EXISTS {}", PROCEDURE_NAME),
+                    format!("CREATE PROCEDURE {}() {}", PROCEDURE_NAME, sql),
+                ],
+                format!("CALL {}.{}()", schema, PROCEDURE_NAME),
+                vec![format!("DROP PROCEDURE IF EXISTS {}.{}", schema, PROCEDURE_NAME)],
+            ),
+        };
+        WrappedQuery {
+            protocol: self,
+            setup,
+            query,
+            teardown,
+        }
+    }
+}
+
+/// Only plain SELECTs are wrapped (mysqltest's view_re/sp_re)
+pub fn is_wrappable(sql: &str) -> bool {
+    let keyword = sql
+        .trim_start()
+        .trim_start_matches(|c: char| c == '(' || c.is_whitespace())
+        .split(|c: char| c.is_whitespace() || c == '(')
+        .next()
+        .unwrap_or("");
+    keyword.eq_ignore_ascii_case("SELECT")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_wrappable() {
+        assert!(is_wrappable("SELECT 1"));
+        assert!(is_wrappable("  select * from t1"));
+        assert!(is_wrappable("(SELECT a FROM t1) UNION (SELECT a FROM t2)"));
+        assert!(!is_wrappable("INSERT INTO t1 SELECT * FROM t2"));
+        assert!(!is_wrappable("SHOW TABLES"));
+        assert!(!is_wrappable("SELECTION"));
+    }
+
+    #[test]
+    fn test_wrap() {
+        let view = WrapProtocol::View.wrap("SELECT a FROM t1;", "test_x");
+        assert_eq!(
+            view.setup,
+            vec![
+                "USE `test_x`",
+                "CREATE OR REPLACE VIEW mysqltest_tmp_v AS SELECT a FROM t1",
+            ]
+        );
+        assert_eq!(view.query, "SELECT * FROM `test_x`.mysqltest_tmp_v");
+        assert_eq!(view.teardown, vec!["DROP VIEW IF EXISTS `test_x`.mysqltest_tmp_v"]);
+
+        let sp = WrapProtocol::StoredProcedure.wrap("SELECT 1", "te`st");
+        assert_eq!(sp.setup[2], "CREATE PROCEDURE mysqltest_tmp_sp() SELECT 1");
+        assert_eq!(sp.query, "CALL `te``st`.mysqltest_tmp_sp()");
+    }
+}
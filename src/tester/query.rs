@@ -1,6 +1,8 @@
 //! The representation of a query.
 //! A .test file is a collection of queries.
 
+use crate::tester::compare::CompareOptions;
+use crate::tester::handlers::json_result::JsonResult;
 use regex::Regex;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -18,6 +20,9 @@ pub enum QueryType {
     Let,
     Eval,
     Require,
+    RequireEngine,
+    RequireVersion,
+    RequireVariable,
     Source,
     Comment,
     Unknown,
@@ -28,16 +33,35 @@ pub enum QueryType {
     DisableQueryLog,
     EnableQueryLog,
     DisableResultLog,
+    DisableColumnNames,
+    EnableColumnNames,
+    EnableWarnings,
+    DisableWarnings,
+    EnableInfo,
+    DisableInfo,
+    EnableMetadata,
+    DisableMetadata,
+    EnablePsProtocol,
+    DisablePsProtocol,
     EnableResultLog,
     SortedResult,
     EnableSortResult,
     DisableSortResult,
+    Compare,
+    EnableCompare,
+    DisableCompare,
+    JsonResult,
+    EnableJsonResult,
+    DisableJsonResult,
+    ValueFormat,
+    CharacterSet,
     ChangeUser,
     EndOfFile,
     BeginConcurrent,
     EndConcurrent,
     Concurrent,
     VerticalResults,
+    QueryVertical,
     HorizontalResults,
     Send,
     Recv,
@@ -53,7 +77,14 @@ pub enum QueryType {
     Exit,
     // Control flow commands
     If,
+    ElseIf,
+    Else,
     While,
+    Foreach,
+    Break,
+    Continue,
+    MaxLoopIterations,
+    Timeout,
     End,
     CloseBrace, // } for closing control flow blocks
     
@@ -81,8 +112,32 @@ pub struct QueryOptions {
     pub expected_errors: Vec<String>,
     /// --replace_regex 收集的替换规则（顺序保持）
     pub replace_regex: Vec<(Regex, String)>,
+    /// --replace_column 收集的列替换规则（列号从 0 开始）
+    pub replace_column: Vec<(usize, String)>,
+    /// --replace_result 收集的字面量替换对（from, to）
+    pub replace_result: Vec<(String, String)>,
     /// 是否对结果进行排序 (--sorted_result)
     pub sorted_result: bool,
+    /// --compare 设置的容差比较选项
+    pub compare: Option<CompareOptions>,
+    /// --json_result 设置的 JSON 规范化输出与结构化比较
+    pub json_result: Option<JsonResult>,
+    /// 是否纵向输出结果 (--query_vertical 或 --vertical_results)
+    pub vertical: bool,
+}
+
+impl QueryOptions {
+    /// 没有任何修饰符时返回 true
+    pub fn is_empty(&self) -> bool {
+        self.expected_errors.is_empty()
+            && self.replace_regex.is_empty()
+            && self.replace_column.is_empty()
+            && self.replace_result.is_empty()
+            && !self.sorted_result
+            && self.compare.is_none()
+            && self.json_result.is_none()
+            && !self.vertical
+    }
 }
 
 #[derive(Debug, Clone)]
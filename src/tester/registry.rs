@@ -14,12 +14,25 @@ pub static COMMAND_REGISTRY: Lazy<HashMap<&'static str, CommandExecutor>> = Lazy
 
     // Register handlers here.
     m.insert("sleep", handlers::sleep::execute);
+    m.insert("timeout", handlers::timeout::execute);
     m.insert("echo", handlers::echo::execute);
     m.insert("exec", handlers::exec::execute);
     m.insert("connect", handlers::connect::execute);
     m.insert("connection", handlers::connection::execute);
     m.insert("disconnect", handlers::disconnect::execute);
 
+    // Asynchronous statements
+    m.insert("send", handlers::send_reap::send);
+    m.insert("reap", handlers::send_reap::reap);
+
+    // Skip mechanics and capability probes
+    m.insert("skip", handlers::require::skip);
+    m.insert("exit", handlers::require::exit);
+    m.insert("require", handlers::require::require);
+    m.insert("require_engine", handlers::require::require_engine);
+    m.insert("require_version", handlers::require::require_version);
+    m.insert("require_variable", handlers::require::require_variable);
+
     // Log control commands
     m.insert("disable_query_log", handlers::query_log::disable_query_log);
     m.insert("enable_query_log", handlers::query_log::enable_query_log);
@@ -28,15 +41,55 @@ pub static COMMAND_REGISTRY: Lazy<HashMap<&'static str, CommandExecutor>> = Lazy
         handlers::result_log::disable_result_log,
     );
     m.insert("enable_result_log", handlers::result_log::enable_result_log);
+    m.insert(
+        "disable_column_names",
+        handlers::column_names::disable_column_names,
+    );
+    m.insert(
+        "enable_column_names",
+        handlers::column_names::enable_column_names,
+    );
+    m.insert("enable_warnings", handlers::warnings::enable_warnings);
+    m.insert("disable_warnings", handlers::warnings::disable_warnings);
+    m.insert("enable_info", handlers::info::enable_info);
+    m.insert("disable_info", handlers::info::disable_info);
+    m.insert("enable_metadata", handlers::metadata::enable_metadata);
+    m.insert("disable_metadata", handlers::metadata::disable_metadata);
+    m.insert("enable_ps_protocol", handlers::ps_protocol::enable_ps_protocol);
+    m.insert("disable_ps_protocol", handlers::ps_protocol::disable_ps_protocol);
 
     // Result modifiers
     m.insert("sorted_result", handlers::sorted_result::execute);
     m.insert("replace_regex", handlers::replace_regex::execute);
+    m.insert("replace_column", handlers::replace_column::execute);
+    m.insert("replace_result", handlers::replace_result::execute);
+    m.insert("compare", handlers::compare::execute);
+    m.insert("enable_compare", handlers::compare::enable_compare);
+    m.insert("disable_compare", handlers::compare::disable_compare);
+    m.insert("json_result", handlers::json_result::execute);
+    m.insert("enable_json_result", handlers::json_result::enable_json_result);
+    m.insert("disable_json_result", handlers::json_result::disable_json_result);
+    m.insert("value_format", handlers::value_format::execute);
+    m.insert("character_set", handlers::character_set::execute);
     m.insert("error", handlers::error::execute);
+    m.insert(
+        "vertical_results",
+        handlers::vertical_results::enable_vertical_results,
+    );
+    m.insert(
+        "horizontal_results",
+        handlers::vertical_results::disable_vertical_results,
+    );
 
     // Variable commands
     m.insert("let", handlers::let_handler::execute);
     m.insert("eval", handlers::eval::execute);
 
+    // Loop control
+    m.insert(
+        "max_loop_iterations",
+        handlers::max_loop_iterations::execute,
+    );
+
     m
 });
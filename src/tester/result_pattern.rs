@@ -0,0 +1,164 @@
+//! Per-line patterns in result files
+//!
+//! An expected line may contain markers that match nondeterministic output
+//! without a `--replace_regex` in the test:
+//!
+//! | Marker | Matches |
+//! |--------|---------|
+//! | `{{ANY}}` | any text, including nothing |
+//! | `{{INT}}` | an integer, optionally negative |
+//! | `{{UUID}}` | a UUID such as `UUID()` returns |
+//! | `{{TIMESTAMP}}` | `YYYY-MM-DD hh:mm:ss` with optional fraction |
+//! | `{{/regex/}}` | the regular expression |
+//!
+//! Other `{{...}}` text is compared literally.
+
+use crate::util::diff;
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+const INT_PATTERN: &str = r"-?\d+";
+const UUID_PATTERN: &str =
+    r"[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}";
+const TIMESTAMP_PATTERN: &str = r"\d{4}-\d{2}-\d{2}[ T]\d{2}:\d{2}:\d{2}(?:\.\d+)?";
+
+thread_local! {
+    /// Compiled expected lines; None for lines without markers
+    static LINE_PATTERNS: RefCell<HashMap<String, Option<Regex>>> = RefCell::new(HashMap::new());
+}
+
+/// Whether an actual output line matches an expected result file line
+pub fn line_matches(expected: &str, actual: &str) -> bool {
+    if expected == actual {
+        return true;
+    }
+    if !expected.contains("{{") {
+        return false;
+    }
+    LINE_PATTERNS.with(|cache| {
+        cache
+            .borrow_mut()
+            .entry(expected.to_string())
+            .or_insert_with(|| compile_line(expected))
+            .as_ref()
+            .is_some_and(|regex| regex.is_match(actual))
+    })
+}
+
+/// Whether the line contains at least one marker
+pub fn has_markers(line: &str) -> bool {
+    line.contains("{{") && compile_line(line).is_some()
+}
+
+/// Anchored regex for a line with markers; None when it has no (valid) marker
+fn compile_line(line: &str) -> Option<Regex> {
+    let mut pattern = String::from("^");
+    let mut found_marker = false;
+    let mut rest = line;
+
+    while let Some(start) = rest.find("{{") {
+        pattern.push_str(&regex::escape(&rest[..start]));
+        let after = &rest[start + 2..];
+
+        let marker = if let Some(body) = after.strip_prefix('/') {
+            body.find("/}}")
+                .map(|end| (format!("(?:{})", &body[..end]), end + 4))
+        } else {
+            after.find("}}").and_then(|end| {
+                let regex = match &after[..end] {
+                    "ANY" => ".*",
+                    "INT" => INT_PATTERN,
+                    "UUID" => UUID_PATTERN,
+                    "TIMESTAMP" => TIMESTAMP_PATTERN,
+                    _ => return None,
+                };
+                Some((regex.to_string(), end + 2))
+            })
+        };
+
+        match marker {
+            Some((regex, consumed)) => {
+                pattern.push_str(&regex);
+                found_marker = true;
+                rest = &after[consumed..];
+            }
+            None => {
+                // Not a marker: keep the braces literally
+                pattern.push_str(&regex::escape("{{"));
+                rest = after;
+            }
+        }
+    }
+    pattern.push_str(&regex::escape(rest));
+    pattern.push('$');
+
+    if !found_marker {
+        return None;
+    }
+    Regex::new(&pattern).ok()
+}
+
+/// The actual output with every line that matches a marker line of the
+/// expected output replaced by that marker line. Recording and reviewing use
+/// it so markers survive when the output still matches them.
+pub fn preserve_markers(expected: &str, actual: &str) -> String {
+    if !expected.contains("{{") {
+        return actual.to_string();
+    }
+    let expected_lines: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+    let mut lines = actual_lines.clone();
+    for (i, j) in diff::matching_lines(&expected_lines, &actual_lines, &line_matches) {
+        lines[j] = expected_lines[i];
+    }
+
+    let mut preserved = lines.join("\n");
+    if actual.ends_with('\n') {
+        preserved.push('\n');
+    }
+    preserved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markers() {
+        assert!(line_matches("id\t{{INT}}", "id\t42"));
+        assert!(line_matches("id\t{{INT}}", "id\t-7"));
+        assert!(!line_matches("id\t{{INT}}", "id\tabc"));
+        assert!(line_matches("{{UUID}}", "6ccd780c-baba-1026-9564-5b8c656024db"));
+        assert!(!line_matches("{{UUID}}", "6ccd780c"));
+        assert!(line_matches("at {{TIMESTAMP}}", "at 2024-01-02 03:04:05"));
+        assert!(line_matches("at {{TIMESTAMP}}", "at 2024-01-02 03:04:05.123456"));
+        assert!(line_matches("Query OK{{ANY}}", "Query OK, 3 rows affected"));
+        assert!(line_matches("v{{/[a-c]+/}}.", "vabc."));
+        assert!(!line_matches("v{{/[a-c]+/}}.", "vabd."));
+    }
+
+    #[test]
+    fn test_literal_text_around_markers() {
+        // Regex characters outside markers are literal
+        assert!(line_matches("a.b {{INT}}", "a.b 1"));
+        assert!(!line_matches("a.b {{INT}}", "axb 1"));
+        // Unknown markers are compared literally
+        assert!(line_matches("{{NAME}}", "{{NAME}}"));
+        assert!(!line_matches("{{NAME}}", "x"));
+        assert!(!has_markers("{\"a\": {{NAME}}}"));
+        assert!(has_markers("id {{INT}}"));
+    }
+
+    #[test]
+    fn test_preserve_markers() {
+        let expected = "SELECT UUID()\nUUID()\n{{UUID}}\nSELECT 1\n1\n";
+        let actual = "SELECT UUID()\nUUID()\n6ccd780c-baba-1026-9564-5b8c656024db\nSELECT 2\n2\n";
+        assert_eq!(
+            preserve_markers(expected, actual),
+            "SELECT UUID()\nUUID()\n{{UUID}}\nSELECT 2\n2\n"
+        );
+        // A marker that no longer matches is replaced by the new output
+        assert_eq!(preserve_markers("{{INT}}\n", "abc\n"), "abc\n");
+    }
+}
@@ -0,0 +1,124 @@
+//! Interactive review of result changes (--review)
+//!
+//! Each changed hunk between the result file and the new output is shown and
+//! can be accepted (`y`), rejected (`n`), or accepted together with all the
+//! remaining hunks of the file (`a`). Only accepted hunks are written back.
+
+use crate::util::diff::{self, Hunk};
+use anyhow::Result;
+use std::io::{BufRead, Write};
+
+/// What the reviewer decided for one hunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Decision {
+    Accept,
+    Reject,
+    AcceptAll,
+}
+
+/// Walk through the changes of one result file. Returns the merged content
+/// when at least one hunk was accepted.
+pub fn review_changes<R: BufRead, W: Write>(
+    name: &str,
+    expected: &str,
+    actual: &str,
+    input: &mut R,
+    output: &mut W,
+) -> Result<Option<String>> {
+    let hunks = diff::hunks(expected, actual, 3);
+    if hunks.is_empty() {
+        return Ok(None);
+    }
+
+    writeln!(output)?;
+    writeln!(output, "Reviewing {} ({} changed hunks)", name, hunks.len())?;
+    let mut accepted = vec![false; hunks.len()];
+    for (index, hunk) in hunks.iter().enumerate() {
+        match ask(index, &hunks, hunk, input, output)? {
+            Decision::Accept => accepted[index] = true,
+            Decision::Reject => {}
+            Decision::AcceptAll => {
+                accepted[index..].iter_mut().for_each(|accept| *accept = true);
+                break;
+            }
+        }
+    }
+
+    let count = accepted.iter().filter(|accept| **accept).count();
+    writeln!(output, "{}: {} of {} hunks accepted", name, count, hunks.len())?;
+    if count == 0 {
+        return Ok(None);
+    }
+    Ok(Some(diff::apply_hunks(expected, actual, &hunks, &accepted)))
+}
+
+fn ask<R: BufRead, W: Write>(
+    index: usize,
+    hunks: &[Hunk],
+    hunk: &Hunk,
+    input: &mut R,
+    output: &mut W,
+) -> Result<Decision> {
+    write!(output, "[{}/{}]\n{}", index + 1, hunks.len(), hunk.text)?;
+    loop {
+        write!(
+            output,
+            "Accept this change? [y]es / [n]o / [a]ccept all remaining in this file: "
+        )?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        // End of input rejects the rest of the changes
+        if input.read_line(&mut answer)? == 0 {
+            writeln!(output)?;
+            return Ok(Decision::Reject);
+        }
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => return Ok(Decision::Accept),
+            "n" | "no" => return Ok(Decision::Reject),
+            "a" | "all" => return Ok(Decision::AcceptAll),
+            _ => writeln!(output, "Please answer y, n or a")?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPECTED: &str = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+    const ACTUAL: &str = "one\n2\n3\n4\n5\n6\n7\n8\n9\nten\n";
+
+    fn review(answers: &str) -> Option<String> {
+        let mut input = answers.as_bytes();
+        let mut output = Vec::new();
+        review_changes("r/t.result", EXPECTED, ACTUAL, &mut input, &mut output).unwrap()
+    }
+
+    #[test]
+    fn test_accept_and_reject_hunks() {
+        assert_eq!(review("y\nn\n").unwrap(), "one\n2\n3\n4\n5\n6\n7\n8\n9\n10\n");
+        assert_eq!(review("n\ny\n").unwrap(), "1\n2\n3\n4\n5\n6\n7\n8\n9\nten\n");
+        assert_eq!(review("n\nn\n"), None);
+    }
+
+    #[test]
+    fn test_accept_all_remaining() {
+        assert_eq!(review("a\n").unwrap(), ACTUAL);
+    }
+
+    #[test]
+    fn test_invalid_answer_and_end_of_input() {
+        // "maybe" is asked again; end of input rejects the second hunk
+        assert_eq!(review("maybe\ny\n").unwrap(), "one\n2\n3\n4\n5\n6\n7\n8\n9\n10\n");
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let mut output = Vec::new();
+        let result =
+            review_changes("r/t.result", EXPECTED, EXPECTED, &mut "".as_bytes(), &mut output);
+        assert_eq!(result.unwrap(), None);
+        assert!(output.is_empty());
+    }
+}
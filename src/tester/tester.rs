@@ -3,16 +3,29 @@
 //! This module handles the execution of MySQL test cases, including database setup,
 //! query execution, result comparison, and cleanup.
 
-use super::database::ConnectionInfo;
+use super::database::{ColumnMeta, ConnectionInfo, QueryResult};
 use super::expression::ExpressionEvaluator;
 use super::parser::{default_parser};
-use super::query::{Query, QueryType};
+use super::query::{Query, QueryOptions, QueryType};
 use super::variables::VariableContext;
 use crate::cli::Args;
 use crate::tester::command::Command;
+use crate::tester::compare::CompareOptions;
 use crate::tester::connection_manager::ConnectionManager;
+use crate::tester::error_codes;
 use crate::tester::error_handler::MySQLErrorHandler;
+use crate::tester::handlers;
+use crate::tester::handlers::foreach::{ForeachIteration, ForeachSource};
+use crate::tester::handlers::json_result::JsonResult;
+use crate::tester::handlers::warnings::{WarningSettings, SHOW_WARNINGS};
+use crate::tester::protocol::{self, WrapProtocol, WrappedQuery};
 use crate::tester::registry::COMMAND_REGISTRY;
+use crate::tester::result_pattern;
+use crate::tester::review;
+use crate::tester::value_format::ValueFormat;
+use crate::tester::watchdog::{self, HangDiagnostics, Watchdog};
+use crate::util::charset::{self, Charset};
+use crate::util::diff;
 use crate::util::memory_pool::{get_byte_vec, get_string_vec, get_regex_vec, PooledByteVec, PooledStringVec, PooledRegexVec};
 use anyhow::{anyhow, Result};
 use chrono;
@@ -25,21 +38,32 @@ use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-/// Maximum number of loop iterations to prevent infinite loops
-const MAX_LOOP_ITERATIONS: usize = 10_000;
-
-/// Control flow frame for while loops
+/// Control flow frame for while and foreach loops
 #[derive(Debug, Clone)]
 struct WhileFrame {
-    /// Start index of the while loop (index of the while command)
+    /// Start index of the loop (index of the while/foreach command)
     start_index: usize,
+    /// Line of the while command in the test file
+    line: usize,
     /// End index of the while loop (index of the matching end command)
     end_index: usize,
     /// The condition expression to evaluate
     condition: String,
     /// Current iteration count
     iteration_count: usize,
+    /// Remaining values of a foreach loop (None for while loops)
+    foreach: Option<ForeachIteration>,
+}
+
+/// Request to stop the current test before the end of the file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopRequest {
+    /// --exit: stop cleanly, output produced so far is still recorded/verified
+    Exit,
+    /// --skip or a failed --require probe: mark the test as skipped
+    Skip(String),
 }
 
 /// Test execution engine
@@ -66,12 +90,65 @@ pub struct Tester {
     result_file_content: Option<String>,
     /// Current position in result file
     current_result_line: usize,
+    /// Set at a difference from the result file; later output is only recorded
+    /// (until the comparison resynchronises under --continue-on-mismatch)
+    result_mismatch: bool,
+    /// Command during which the last difference was found
+    mismatch_command: usize,
+    /// Number of commands executed so far; a new value marks a statement boundary
+    command_counter: usize,
+    /// Differences collected under --continue-on-mismatch
+    mismatch_failures: Vec<QueryFailureDetail>,
 
     // --- One-shot modifiers for the next query ---
     /// Sort results for the next query
     pub pending_sorted_result: bool,
+    /// Tolerant comparison options for the next query (--compare)
+    pub pending_compare: Option<CompareOptions>,
+    /// Canonical JSON output and structural comparison for the next query (--json_result)
+    pub pending_json_result: Option<JsonResult>,
     /// Regex for result replacement for the next query (memory pool optimized)
     pub pending_replace_regex: PooledRegexVec,
+    /// Column replacements (0-based index, value) for the next query
+    pub pending_replace_column: Vec<(usize, String)>,
+    /// Literal (from, to) replacements for the next query
+    pub pending_replace_result: Vec<(String, String)>,
+    /// Print the next query result vertically (--query_vertical)
+    pending_vertical: bool,
+    /// Send the next SQL statement instead of executing it (`--send` without arguments)
+    pub pending_send: bool,
+    /// Compare the next query output with this file and skip on mismatch (`--require <file>`)
+    pub pending_require: Option<PathBuf>,
+    /// Set by --exit, --skip and failed --require probes, checked after every query
+    pub stop_request: Option<StopRequest>,
+    /// Statements started with --send, keyed by connection name, with their modifiers
+    sent_statements: HashMap<String, Query>,
+    /// Persistent vertical result mode (--vertical_results / --horizontal_results)
+    pub vertical_results: bool,
+    /// Tolerant comparison options until the end of the test (--enable_compare / --disable_compare)
+    pub compare_options: CompareOptions,
+    /// Canonical JSON output for JSON columns (--enable_json_result / --disable_json_result)
+    pub json_result: Option<JsonResult>,
+    /// Rendering of binary values and NULL (--value_format)
+    pub value_format: ValueFormat,
+    /// Encoding of the running test file, also used for its result file
+    file_charset: Charset,
+    /// Print a column name header per result set (--enable/--disable_column_names)
+    pub display_column_names: bool,
+    /// Warning capture after each statement (--enable_warnings / --disable_warnings)
+    pub warnings: WarningSettings,
+    /// Print affected rows and server info after each statement (--enable_info / --disable_info)
+    pub display_info: bool,
+    /// Print column metadata before each result set (--enable_metadata / --disable_metadata)
+    pub display_metadata: bool,
+    /// Run statements through prepare/execute (--ps-protocol, --enable_ps_protocol)
+    pub ps_protocol: bool,
+    /// Wrap SELECTs in a view or procedure (--view-protocol / --sp-protocol)
+    pub wrap_protocol: Option<WrapProtocol>,
+    /// Kill statements running longer than this (--query-timeout, overridable with --timeout)
+    pub query_timeout: Option<Duration>,
+    /// When the running test file has to be finished (--test-timeout)
+    test_deadline: Option<Instant>,
     /// Variable context for storing test variables
     pub variable_context: VariableContext,
 
@@ -80,6 +157,8 @@ pub struct Tester {
     expression_evaluator: ExpressionEvaluator,
     /// Stack of active while loops
     while_stack: Vec<WhileFrame>,
+    /// Maximum iterations per while loop (--max-loop-iterations, overridable in-file)
+    pub max_loop_iterations: usize,
     /// Mapping from start index to end index for control structures
     control_flow_map: HashMap<usize, usize>,
     /// Flag to indicate if we are inside a concurrent block
@@ -130,12 +209,17 @@ impl Tester {
             user: args.user.clone(),
             password: args.passwd.clone(),
             database: "".to_string(), // Start with no specific database
-            params: args.params.clone(),
+            params: connection_params(&args),
         };
 
         // Create connection manager with default connection
         let connection_manager =
             ConnectionManager::new(connection_info, args.retry_conn_count as u32)?;
+        let display_column_names = !args.legacy_result_format;
+        let max_loop_iterations = args.max_loop_iterations;
+        let ps_protocol = args.ps_protocol;
+        let wrap_protocol = WrapProtocol::from_args(&args);
+        let query_timeout = args.query_timeout.filter(|timeout| !timeout.is_zero());
 
         Ok(Tester {
             connection_manager,
@@ -149,11 +233,38 @@ impl Tester {
             error_handler: MySQLErrorHandler::new(),
             result_file_content: None,
             current_result_line: 1, // Line numbers are 1-based
+            result_mismatch: false,
+            mismatch_command: 0,
+            command_counter: 0,
+            mismatch_failures: Vec::new(),
             pending_sorted_result: false,
+            pending_compare: None,
+            pending_json_result: None,
             pending_replace_regex: get_regex_vec(),
+            pending_replace_column: Vec::new(),
+            pending_replace_result: Vec::new(),
+            pending_vertical: false,
+            pending_send: false,
+            pending_require: None,
+            stop_request: None,
+            sent_statements: HashMap::new(),
+            vertical_results: false,
+            compare_options: CompareOptions::default(),
+            json_result: None,
+            value_format: ValueFormat::default(),
+            file_charset: Charset::default(),
+            display_column_names,
+            warnings: WarningSettings::default(),
+            display_info: false,
+            display_metadata: false,
+            ps_protocol,
+            wrap_protocol,
+            query_timeout,
+            test_deadline: None,
             variable_context: VariableContext::new(),
             expression_evaluator: ExpressionEvaluator::new(),
             while_stack: Vec::new(),
+            max_loop_iterations,
             control_flow_map: HashMap::new(),
             in_concurrent_block: false,
             concurrent_queries: Vec::new(),
@@ -176,11 +287,36 @@ impl Tester {
         self.output_buffer.clear();
         self.expected_errors.clear();
         self.current_result_line = 1;
+        self.result_mismatch = false;
+        self.mismatch_failures.clear();
         self.pending_sorted_result = false;
+        self.pending_compare = None;
+        self.pending_json_result = None;
         self.pending_replace_regex.clear();
+        self.pending_replace_column.clear();
+        self.pending_replace_result.clear();
+        self.pending_vertical = false;
+        self.pending_send = false;
+        self.pending_require = None;
+        self.stop_request = None;
+        self.sent_statements.clear();
+        self.vertical_results = false;
+        self.compare_options = CompareOptions::default();
+        self.json_result = None;
+        if self.value_format != ValueFormat::default() {
+            self.value_format = ValueFormat::default();
+            self.connection_manager.set_value_format(ValueFormat::default());
+        }
+        self.display_column_names = !self.args.legacy_result_format;
+        self.warnings = WarningSettings::default();
+        self.display_info = false;
+        self.display_metadata = false;
+        self.ps_protocol = self.args.ps_protocol;
+        self.query_timeout = self.args.query_timeout.filter(|timeout| !timeout.is_zero());
 
         // Clear control flow state
         self.while_stack.clear();
+        self.max_loop_iterations = self.args.max_loop_iterations;
         self.control_flow_map.clear();
         self.in_concurrent_block = false;
         self.concurrent_queries.clear();
@@ -201,7 +337,22 @@ impl Tester {
 
         // Pre-process: setup database state
         self.pre_process()?;
+        self.set_run_variables()?;
+
+        Ok(())
+    }
 
+    /// Predefine run-level variables such as $MYSQL_VERSION and $CURRENT_TEST
+    fn set_run_variables(&mut self) -> Result<()> {
+        let version = self.connection_manager.current_database()?.server_version()?;
+
+        self.variable_context.set("MYSQL_VERSION", version);
+        self.variable_context.set("CURRENT_TEST", self.test_name.clone());
+        // Created once at startup (Args::prepare_vardir)
+        self.variable_context
+            .set("MYSQLTEST_VARDIR", self.args.vardir.clone());
+        self.variable_context.set("MASTER_MYPORT", self.args.port.clone());
+        self.variable_context.set("MYSQL", self.args.mysql_client.clone());
         Ok(())
     }
 
@@ -273,8 +424,11 @@ impl Tester {
         }
 
         // Read and parse the test file
-        let content = match fs::read_to_string(&test_path) {
-            Ok(content) => content,
+        let content = match charset::read_text_file(&test_path, self.args.file_charset) {
+            Ok((content, file_charset)) => {
+                self.file_charset = file_charset;
+                content
+            }
             Err(e) => {
                 result.add_error(format!(
                     "Failed to read test file {}: {}",
@@ -311,13 +465,29 @@ impl Tester {
         }
 
         // Execute queries with control flow support
+        self.test_deadline = self
+            .args
+            .test_timeout
+            .filter(|timeout| !timeout.is_zero())
+            .map(|timeout| Instant::now() + timeout);
         let mut pc = 0;
         while pc < queries.len() {
             let query = &queries[pc];
+            if self.test_timed_out() {
+                result.add_error(format!(
+                    "Test timed out after {} before line {}",
+                    watchdog::format_duration(self.args.test_timeout.unwrap_or_default()),
+                    query.line
+                ));
+                break;
+            }
             match self.execute_query_with_control_flow(query, pc, &queries) {
                 Ok(next_pc) => {
                     result.passed_queries += 1;
                     pc = next_pc;
+                    if self.stop_request.is_some() {
+                        break;
+                    }
                 }
                 Err(e) => {
                     result.failed_queries += 1;
@@ -327,7 +497,7 @@ impl Tester {
                     // Record detailed failure information for Allure
                     self.record_query_failure(&mut result, &e);
 
-                    if self.args.fail_fast {
+                    if self.args.fail_fast || self.test_timed_out() {
                         break;
                     }
                     pc += 1;
@@ -335,13 +505,47 @@ impl Tester {
             }
         }
 
+        // Differences collected under --continue-on-mismatch fail the test
+        for failure in std::mem::take(&mut self.mismatch_failures) {
+            result.failed_queries += 1;
+            result.add_error(failure.error_message.clone());
+            result.add_query_failure(failure);
+        }
+
+        let skip_reason = match self.stop_request.take() {
+            Some(StopRequest::Skip(reason)) => Some(reason),
+            Some(StopRequest::Exit) => {
+                debug!("Test '{}' stopped by --exit", test_name);
+                None
+            }
+            None => None,
+        };
+
+        // Statements started with --send must be reaped; wait for leftovers so
+        // their connections are returned before cleanup
+        for conn_name in self.connection_manager.pending_connections() {
+            let _ = self.connection_manager.reap(&conn_name);
+            if skip_reason.is_none() {
+                result.add_error(format!(
+                    "Statement sent on connection '{}' was never reaped",
+                    conn_name
+                ));
+            }
+        }
+
         // Post-process: cleanup database state
         if let Err(e) = self.post_process() {
             result.add_error(format!("Post-process failed: {}", e));
         }
 
-        // Write result file if in record mode
-        if self.args.record {
+        if let Some(reason) = skip_reason {
+            // A skipped test has no complete output: neither record nor verify it
+            info!("Test '{}' skipped: {}", test_name, reason);
+            if result.errors.is_empty() {
+                result.mark_skipped(reason);
+            }
+        } else if self.args.record {
+            // Write result file in record mode
             if let Err(e) = self.write_result_file(&test_name) {
                 result.add_error(format!("Failed to write result file: {}", e));
             }
@@ -358,6 +562,14 @@ impl Tester {
                     debug!("Skipping result verification due to previous query failures");
                 }
             }
+
+            match self.write_reject_file(&test_name) {
+                Ok(diff) => result.diff = diff,
+                Err(e) => warn!("Failed to write reject file for '{}': {}", test_name, e),
+            }
+            if let Err(e) = self.review_result_changes(&test_name) {
+                result.add_error(format!("Failed to review result changes: {}", e));
+            }
         }
 
         // Set final result status
@@ -377,31 +589,76 @@ impl Tester {
         Ok(result)
     }
 
+    /// Load one-shot modifiers bound to a query into the pending state
+    fn inject_query_options(&mut self, options: &QueryOptions) {
+        if !options.expected_errors.is_empty() {
+            self.expected_errors = options.expected_errors.iter().cloned().collect();
+        }
+        if !options.replace_regex.is_empty() {
+            self.pending_replace_regex.clear();
+            for (regex, replacement) in &options.replace_regex {
+                self.pending_replace_regex.push(regex.clone(), replacement.clone());
+            }
+        }
+        if !options.replace_column.is_empty() {
+            self.pending_replace_column = options.replace_column.clone();
+        }
+        if !options.replace_result.is_empty() {
+            self.pending_replace_result = options.replace_result.clone();
+        }
+        if options.sorted_result {
+            self.pending_sorted_result = true;
+        }
+        if options.compare.is_some() {
+            self.pending_compare = options.compare.clone();
+        }
+        if options.json_result.is_some() {
+            self.pending_json_result = options.json_result;
+        }
+        if options.vertical {
+            self.pending_vertical = true;
+        }
+    }
+
+    /// Move the pending one-shot modifiers into a `QueryOptions`, leaving them cleared
+    fn take_pending_options(&mut self) -> QueryOptions {
+        let options = QueryOptions {
+            expected_errors: self.expected_errors.iter().cloned().collect(),
+            replace_regex: self.pending_replace_regex.to_vec(),
+            replace_column: std::mem::take(&mut self.pending_replace_column),
+            replace_result: std::mem::take(&mut self.pending_replace_result),
+            sorted_result: self.pending_sorted_result,
+            compare: self.pending_compare.take(),
+            json_result: self.pending_json_result.take(),
+            vertical: self.pending_vertical,
+        };
+        self.expected_errors.clear();
+        self.pending_replace_regex.clear();
+        self.pending_sorted_result = false;
+        self.pending_vertical = false;
+        options
+    }
+
     /// Execute a single query and handle its result
     fn execute_query(&mut self, query: &Query, query_num: usize) -> Result<()> {
+        self.command_counter += 1;
         debug!(
             "Executing query {} (line {}): {:?} - '{}'",
             query_num, query.line, query.query_type, query.query
         );
 
         // 注入绑定在 Query 上的一次性修饰符
-        if !query.options.expected_errors.is_empty() {
-            self.expected_errors = query.options.expected_errors.iter().cloned().collect();
-        }
-        if !query.options.replace_regex.is_empty() {
-            self.pending_replace_regex.clear();
-            for (regex, replacement) in &query.options.replace_regex {
-                self.pending_replace_regex.push(regex.clone(), replacement.clone());
-            }
-        }
-        if query.options.sorted_result {
-            self.pending_sorted_result = true;
-        }
+        self.inject_query_options(&query.options);
 
         match query.query_type {
             QueryType::Query => {
-                // Check if we're in batch mode
-                if let Some(_) = &self.batch_mode {
+                if self.pending_send {
+                    // `--send` without arguments: send this statement instead of running it
+                    self.pending_send = false;
+                    self.send_sql_query(&query.query, query.line)?;
+                } else if let Some(require_file) = self.pending_require.take() {
+                    self.execute_require_query(&query.query, &require_file)?;
+                } else if let Some(_) = &self.batch_mode {
                     // Add to batch instead of executing immediately
                     self.add_to_batch(query.query.clone())?;
                 } else {
@@ -411,7 +668,56 @@ impl Tester {
                 // Modifiers are one-shot, clear them after the SQL query runs.
                 self.expected_errors.clear();
                 self.pending_sorted_result = false;
+                self.pending_compare = None;
+                self.pending_json_result = None;
+                self.pending_replace_regex.clear();
+                self.pending_replace_column.clear();
+                self.pending_replace_result.clear();
+            }
+            QueryType::Send | QueryType::Recv => {
+                let name = if query.query_type == QueryType::Send {
+                    "send"
+                } else {
+                    "reap"
+                };
+                let cmd = Command {
+                    name: name.to_string(),
+                    args: query.query.clone(),
+                    line: query.line,
+                };
+
+                if let Some(executor) = COMMAND_REGISTRY.get(cmd.name.as_str()) {
+                    executor(self, &cmd)?;
+                } else {
+                    return Err(anyhow!("'{}' command handler not found in registry", name));
+                }
+
+                // `--send` without arguments keeps the modifiers for the statement it sends
+                if !self.pending_send {
+                    self.expected_errors.clear();
+                    self.pending_sorted_result = false;
+                    self.pending_compare = None;
+                    self.pending_json_result = None;
+                    self.pending_replace_regex.clear();
+                    self.pending_replace_column.clear();
+                    self.pending_replace_result.clear();
+                }
+            }
+            QueryType::QueryVertical => {
+                // One-shot vertical output for this statement only
+                let sql = query.query.trim_end().trim_end_matches(';');
+                self.pending_vertical = true;
+                let result = self.execute_sql_query(sql, query.line);
+                self.pending_vertical = false;
+                result?;
+
+                self.expected_errors.clear();
+                self.pending_sorted_result = false;
+                self.pending_compare = None;
+                self.pending_json_result = None;
                 self.pending_replace_regex.clear();
+                self.pending_replace_column.clear();
+                self.pending_replace_result.clear();
             }
             QueryType::Exec => {
                 // Create a command object to use the new handler system
@@ -431,7 +737,11 @@ impl Tester {
                 // Clear modifiers and expected errors after exec command
                 self.expected_errors.clear();
                 self.pending_sorted_result = false;
+                self.pending_compare = None;
+                self.pending_json_result = None;
                 self.pending_replace_regex.clear();
+                self.pending_replace_column.clear();
+                self.pending_replace_result.clear();
             }
             QueryType::Comment => {
                 // Skip comments
@@ -590,6 +900,32 @@ impl Tester {
                     self.parse_replace_regex(&query.query)?;
                 }
             }
+            QueryType::Replace => {
+                let cmd = Command {
+                    name: "replace_result".to_string(),
+                    args: query.query.clone(),
+                    line: query.line,
+                };
+
+                if let Some(executor) = COMMAND_REGISTRY.get(cmd.name.as_str()) {
+                    executor(self, &cmd)?;
+                } else {
+                    return Err(anyhow!("'replace_result' command handler not found in registry"));
+                }
+            }
+            QueryType::ReplaceColumn => {
+                let cmd = Command {
+                    name: "replace_column".to_string(),
+                    args: query.query.clone(),
+                    line: query.line,
+                };
+
+                if let Some(executor) = COMMAND_REGISTRY.get(cmd.name.as_str()) {
+                    executor(self, &cmd)?;
+                } else {
+                    return Err(anyhow!("'replace_column' command handler not found in registry"));
+                }
+            }
             QueryType::Error => {
                 let cmd = Command {
                     name: "error".to_string(),
@@ -600,7 +936,7 @@ impl Tester {
                 if let Some(executor) = COMMAND_REGISTRY.get(cmd.name.as_str()) {
                     executor(self, &cmd)?;
                 } else {
-                    self.parse_expected_errors(&query.query)?;
+                    self.parse_expected_errors(&query.query, query.line)?;
                 }
             }
 
@@ -750,6 +1086,189 @@ impl Tester {
                 use crate::tester::handlers::transaction_operations::execute_rollback_transaction;
                 execute_rollback_transaction(self, &query.query)?;
             }
+            QueryType::DisableColumnNames | QueryType::EnableColumnNames => {
+                let name = if query.query_type == QueryType::DisableColumnNames {
+                    "disable_column_names"
+                } else {
+                    "enable_column_names"
+                };
+                let cmd = Command {
+                    name: name.to_string(),
+                    args: query.query.clone(),
+                    line: query.line,
+                };
+
+                if let Some(executor) = COMMAND_REGISTRY.get(cmd.name.as_str()) {
+                    executor(self, &cmd)?;
+                } else {
+                    return Err(anyhow!("'{}' command handler not found in registry", name));
+                }
+
+                if !self.expected_errors.is_empty() {
+                    warn!("--error directive before --{} is ignored", name);
+                    self.expected_errors.clear();
+                }
+            }
+            QueryType::EnableWarnings
+            | QueryType::DisableWarnings
+            | QueryType::EnableInfo
+            | QueryType::DisableInfo
+            | QueryType::EnableMetadata
+            | QueryType::DisableMetadata
+            | QueryType::EnablePsProtocol
+            | QueryType::DisablePsProtocol => {
+                let name = match query.query_type {
+                    QueryType::EnableWarnings => "enable_warnings",
+                    QueryType::DisableWarnings => "disable_warnings",
+                    QueryType::EnableInfo => "enable_info",
+                    QueryType::DisableInfo => "disable_info",
+                    QueryType::EnableMetadata => "enable_metadata",
+                    QueryType::DisableMetadata => "disable_metadata",
+                    QueryType::EnablePsProtocol => "enable_ps_protocol",
+                    _ => "disable_ps_protocol",
+                };
+                let cmd = Command {
+                    name: name.to_string(),
+                    args: query.query.clone(),
+                    line: query.line,
+                };
+
+                if let Some(executor) = COMMAND_REGISTRY.get(cmd.name.as_str()) {
+                    executor(self, &cmd)?;
+                } else {
+                    return Err(anyhow!("'{}' command handler not found in registry", name));
+                }
+
+                if !self.expected_errors.is_empty() {
+                    warn!("--error directive before --{} is ignored", name);
+                    self.expected_errors.clear();
+                }
+            }
+            QueryType::Compare
+            | QueryType::EnableCompare
+            | QueryType::DisableCompare
+            | QueryType::JsonResult
+            | QueryType::EnableJsonResult
+            | QueryType::DisableJsonResult
+            | QueryType::ValueFormat
+            | QueryType::CharacterSet => {
+                let name = match query.query_type {
+                    QueryType::Compare => "compare",
+                    QueryType::EnableCompare => "enable_compare",
+                    QueryType::DisableCompare => "disable_compare",
+                    QueryType::JsonResult => "json_result",
+                    QueryType::EnableJsonResult => "enable_json_result",
+                    QueryType::DisableJsonResult => "disable_json_result",
+                    QueryType::ValueFormat => "value_format",
+                    _ => "character_set",
+                };
+                let cmd = Command {
+                    name: name.to_string(),
+                    args: query.query.clone(),
+                    line: query.line,
+                };
+
+                if let Some(executor) = COMMAND_REGISTRY.get(cmd.name.as_str()) {
+                    executor(self, &cmd)?;
+                } else {
+                    return Err(anyhow!("'{}' command handler not found in registry", name));
+                }
+
+                if !self.expected_errors.is_empty() {
+                    warn!("--error directive before --{} is ignored", name);
+                    self.expected_errors.clear();
+                }
+            }
+            QueryType::MaxLoopIterations => {
+                let cmd = Command {
+                    name: "max_loop_iterations".to_string(),
+                    args: query.query.clone(),
+                    line: query.line,
+                };
+
+                if let Some(executor) = COMMAND_REGISTRY.get(cmd.name.as_str()) {
+                    executor(self, &cmd)?;
+                } else {
+                    return Err(anyhow!("'max_loop_iterations' command handler not found in registry"));
+                }
+
+                if !self.expected_errors.is_empty() {
+                    warn!("--error directive before --max_loop_iterations is ignored");
+                    self.expected_errors.clear();
+                }
+            }
+            QueryType::Timeout => {
+                let cmd = Command {
+                    name: "timeout".to_string(),
+                    args: query.query.clone(),
+                    line: query.line,
+                };
+
+                if let Some(executor) = COMMAND_REGISTRY.get(cmd.name.as_str()) {
+                    executor(self, &cmd)?;
+                } else {
+                    return Err(anyhow!("'timeout' command handler not found in registry"));
+                }
+
+                if !self.expected_errors.is_empty() {
+                    warn!("--error directive before --timeout is ignored");
+                    self.expected_errors.clear();
+                }
+            }
+            QueryType::Skip
+            | QueryType::Exit
+            | QueryType::Require
+            | QueryType::RequireEngine
+            | QueryType::RequireVersion
+            | QueryType::RequireVariable => {
+                let name = match query.query_type {
+                    QueryType::Skip => "skip",
+                    QueryType::Exit => "exit",
+                    QueryType::Require => "require",
+                    QueryType::RequireEngine => "require_engine",
+                    QueryType::RequireVersion => "require_version",
+                    _ => "require_variable",
+                };
+                let cmd = Command {
+                    name: name.to_string(),
+                    args: query.query.clone(),
+                    line: query.line,
+                };
+
+                if let Some(executor) = COMMAND_REGISTRY.get(cmd.name.as_str()) {
+                    executor(self, &cmd)?;
+                } else {
+                    return Err(anyhow!("'{}' command handler not found in registry", name));
+                }
+
+                if !self.expected_errors.is_empty() {
+                    warn!("--error directive before --{} is ignored", name);
+                    self.expected_errors.clear();
+                }
+            }
+            QueryType::VerticalResults | QueryType::HorizontalResults => {
+                let name = if query.query_type == QueryType::VerticalResults {
+                    "vertical_results"
+                } else {
+                    "horizontal_results"
+                };
+                let cmd = Command {
+                    name: name.to_string(),
+                    args: query.query.clone(),
+                    line: query.line,
+                };
+
+                if let Some(executor) = COMMAND_REGISTRY.get(cmd.name.as_str()) {
+                    executor(self, &cmd)?;
+                } else {
+                    return Err(anyhow!("'{}' command handler not found in registry", name));
+                }
+
+                if !self.expected_errors.is_empty() {
+                    warn!("--error directive before --{} is ignored", name);
+                    self.expected_errors.clear();
+                }
+            }
             _ => {
                 warn!("Unhandled query type: {:?}", query.query_type);
             }
@@ -771,6 +1290,217 @@ impl Tester {
         // Expand variables in the SQL query
         let expanded_sql = self.variable_context.expand(sql)?;
 
+        self.log_query(&expanded_sql)?;
+
+        let wrapped = self.wrap_query(&expanded_sql);
+        let watchdog = self.arm_watchdog();
+        let execution_result = match &wrapped {
+            Some(wrapped) => self.run_wrapped_statement(wrapped, &expanded_sql),
+            None => self.run_statement(&expanded_sql, self.ps_protocol),
+        };
+        let hang = watchdog.and_then(Watchdog::disarm);
+
+        // Warnings are fetched while handling the outcome, so the wrapper is dropped afterwards
+        let outcome = match hang {
+            // The current query is kept so the failure detail names the statement;
+            // an expected --error does not cover a timeout
+            Some(hang) => {
+                self.take_pending_options();
+                Err(anyhow!(hang.describe(&expanded_sql, line_number)))
+            }
+            None => self.handle_query_outcome(execution_result),
+        };
+        if let Some(wrapped) = wrapped {
+            if let Err(e) = self.run_wrapper_statements(&wrapped.teardown) {
+                debug!("Failed to drop {:?} protocol wrapper: {}", wrapped.protocol, e);
+            }
+        }
+        outcome
+    }
+
+    /// Start watching the next statement when a query or test timeout applies
+    fn arm_watchdog(&mut self) -> Option<Watchdog> {
+        let timeout = watchdog::statement_timeout(self.query_timeout, self.test_deadline)?;
+        let database = self.connection_manager.current_database().ok()?;
+        match database.connection_id() {
+            Ok(connection_id) => Some(Watchdog::arm(database.pool(), connection_id, timeout)),
+            Err(e) => {
+                warn!("Statement timeout not enforced, no connection id: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Whether the --test-timeout deadline has passed
+    fn test_timed_out(&self) -> bool {
+        self.test_deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Run a statement on the current connection
+    fn run_statement(&mut self, sql: &str, prepared: bool) -> Result<QueryResult> {
+        let database = self.connection_manager.current_database()?;
+        if prepared {
+            database.query_prepared(sql)
+        } else {
+            database.query_with_metadata(sql)
+        }
+    }
+
+    /// Rewrite a statement for the active wrapping protocol. Returns None when
+    /// it has to run as is.
+    fn wrap_query(&mut self, sql: &str) -> Option<WrappedQuery> {
+        let protocol = self.wrap_protocol?;
+        if !protocol::is_wrappable(sql) {
+            debug!("{:?} protocol: statement not wrapped: {}", protocol, sql);
+            return None;
+        }
+        // Wrapper objects show up in column metadata
+        if self.display_metadata {
+            debug!("{:?} protocol: skipped while metadata is displayed", protocol);
+            return None;
+        }
+
+        let schema = match self
+            .connection_manager
+            .current_database()
+            .and_then(|db| db.current_schema())
+        {
+            Ok(schema) => schema,
+            Err(e) => {
+                debug!("{:?} protocol: cannot read current database ({}), statement not wrapped", protocol, e);
+                return None;
+            }
+        };
+        match schema {
+            Some(schema) => Some(protocol.wrap(sql, &schema)),
+            None => {
+                debug!("{:?} protocol: no current database, statement not wrapped", protocol);
+                None
+            }
+        }
+    }
+
+    /// Create the wrapper object and run the wrapped statement. If either step
+    /// fails, the original statement runs instead, so errors are reported exactly
+    /// as without the protocol.
+    fn run_wrapped_statement(&mut self, wrapped: &WrappedQuery, sql: &str) -> Result<QueryResult> {
+        if let Err(e) = self.run_wrapper_statements(&wrapped.setup) {
+            debug!("{:?} protocol: cannot wrap statement ({}): {}", wrapped.protocol, e, sql);
+            return self.run_statement(sql, self.ps_protocol);
+        }
+
+        match self.run_statement(&wrapped.query, self.ps_protocol) {
+            Ok(result) => Ok(result),
+            Err(e) => {
+                debug!("{:?} protocol: wrapped statement failed ({}): {}", wrapped.protocol, e, sql);
+                self.run_statement(sql, self.ps_protocol)
+            }
+        }
+    }
+
+    /// Run wrapper setup/teardown statements on a helper connection (the
+    /// implicit commit of views and procedures must not affect the test connection)
+    fn run_wrapper_statements(&mut self, statements: &[String]) -> Result<()> {
+        let mut conn = self.connection_manager.current_database()?.get_pooled_connection()?;
+        for statement in statements {
+            conn.query_drop(statement)?;
+        }
+        Ok(())
+    }
+
+    /// Start a statement on the current connection without waiting for it (--send).
+    /// The query is logged now; the result is written when it is reaped.
+    pub fn send_sql_query(&mut self, sql: &str, line_number: usize) -> Result<()> {
+        let sql = sql.trim_end().trim_end_matches(';');
+        self.set_current_query(sql.to_string(), line_number);
+        let expanded_sql = self.variable_context.expand(sql)?;
+
+        self.log_query(&expanded_sql)?;
+        // The watchdog runs until --reap, like the statement itself
+        let watchdog = self.arm_watchdog();
+        self.connection_manager.send(&expanded_sql, self.ps_protocol, watchdog)?;
+
+        let conn_name = self.connection_manager.current_connection_name().to_string();
+        let options = self.take_pending_options();
+        self.sent_statements.insert(
+            conn_name,
+            Query {
+                query_type: QueryType::Send,
+                query: expanded_sql,
+                line: line_number,
+                options,
+            },
+        );
+        self.clear_current_query();
+        Ok(())
+    }
+
+    /// Wait for the statement sent on the current connection and write its result (--reap).
+    /// Modifiers given right before --reap win over the ones captured at --send time.
+    pub fn reap_sql_query(&mut self, line_number: usize) -> Result<()> {
+        let conn_name = self.connection_manager.current_connection_name().to_string();
+        let (sql, execution_result, hang) = self.connection_manager.reap(&conn_name)?;
+        let sent = self.sent_statements.remove(&conn_name);
+
+        let reap_options = self.take_pending_options();
+        let line = match sent {
+            Some(sent) if reap_options.is_empty() => {
+                self.inject_query_options(&sent.options);
+                sent.line
+            }
+            _ => {
+                self.inject_query_options(&reap_options);
+                line_number
+            }
+        };
+        self.set_current_query(sql.clone(), line);
+
+        // As in execute_sql_query, an expected --error does not cover a timeout
+        if let Some(hang) = hang {
+            self.take_pending_options();
+            return Err(anyhow!(hang.describe(&sql, line)));
+        }
+        self.handle_query_outcome(execution_result)
+    }
+
+    /// Connection switches are only allowed into a connection with a pending
+    /// --send statement when the switch is followed by --reap.
+    fn check_switch_to_pending_connection(
+        &self,
+        query: &Query,
+        pc: usize,
+        queries: &[Query],
+    ) -> Result<()> {
+        let conn_name = self.variable_context.expand(&query.query)?;
+        let conn_name = conn_name.trim();
+        if !self.connection_manager.has_pending(conn_name) {
+            return Ok(());
+        }
+
+        let next = queries[pc + 1..].iter().find(|q| {
+            !matches!(
+                q.query_type,
+                QueryType::Comment
+                    | QueryType::Error
+                    | QueryType::SortedResult
+                    | QueryType::ReplaceRegex
+                    | QueryType::ReplaceColumn
+                    | QueryType::Replace
+            )
+        });
+        if next.map(|q| q.query_type) == Some(QueryType::Recv) {
+            return Ok(());
+        }
+
+        Err(anyhow!(
+            "Cannot switch to connection '{}' at line {}: it has a pending statement started by --send; --reap it right after switching",
+            conn_name,
+            query.line
+        ))
+    }
+
+    /// Write the statement to the output when the query log is enabled
+    fn log_query(&mut self, expanded_sql: &str) -> Result<()> {
         if self.enable_query_log {
             let query_output = format!("{}\n", expanded_sql);
             if self.args.record {
@@ -783,72 +1513,224 @@ impl Tester {
                 }
             }
         }
+        Ok(())
+    }
 
-        let execution_result = self
-            .connection_manager
-            .current_database()?
-            .query(&expanded_sql);
+    /// Write the result of a statement (or check its expected error)
+    fn handle_query_outcome(&mut self, execution_result: Result<QueryResult>) -> Result<()> {
+        self.update_status_variables(&execution_result);
+
+        let outcome = match execution_result {
+            // A later statement of a multi-statement query may have failed: the
+            // result sets before it are written, then its error is checked
+            Ok(mut result) => match result.error.take() {
+                Some(e) => self
+                    .write_query_result(&result, false)
+                    .and_then(|_| self.check_query_error(e)),
+                None => self.write_query_result(&result, true),
+            },
+            Err(e) => self.check_query_error(e),
+        };
+        self.clear_current_query();
+        outcome
+    }
 
-        match execution_result {
-            Ok(rows) => {
-                if !self.expected_errors.is_empty() {
-                    let err_msg = format!(
-                        "Expected error(s) {:?}, but query succeeded",
-                        self.expected_errors
-                    );
-                    if self.args.check_err {
-                        self.clear_current_query();
-                        return Err(anyhow!(err_msg));
-                    } else {
-                        warn!("{}", err_msg);
-                    }
-                }
+    /// Write every result set of a statement. Warnings are listed after the
+    /// last one (SHOW WARNINGS only reports the last statement); earlier sets
+    /// show their warning count. `complete` is false when a later statement of
+    /// the batch failed.
+    fn write_query_result(&mut self, result: &QueryResult, complete: bool) -> Result<()> {
+        if complete && !self.expected_errors.is_empty() {
+            let err_msg = format!(
+                "Expected error(s) {:?}, but query succeeded",
+                self.expected_errors
+            );
+            if self.args.check_err {
+                return Err(anyhow!(err_msg));
+            } else {
+                warn!("{}", err_msg);
+            }
+        }
 
-                if self.enable_result_log {
-                    let mut formatted_result = self.format_query_result_to_string(&rows)?;
-                    self.apply_regex_replacements(&mut formatted_result);
+        if !self.enable_result_log {
+            return Ok(());
+        }
+        // Result cells are replaced one by one while formatting; replacements
+        // apply to the surrounding metadata, info and warnings as a whole
+        let mut formatted_result = String::new();
+        let set_count = result.more_results.len() + 1;
+        for (index, set) in result.result_sets().enumerate() {
+            if self.display_metadata {
+                let mut metadata = handlers::metadata::format_metadata(&set.columns, &set.rows);
+                self.apply_replacements(&mut metadata);
+                formatted_result.push_str(&metadata);
+            }
+            formatted_result.push_str(&self.format_query_result_to_string(&set.columns, &set.rows)?);
+            let mut trailer = String::new();
+            if self.display_info {
+                trailer.push_str(&handlers::info::format_info(&set.execution_info));
+            }
+            if self.warnings.enabled && index + 1 < set_count {
+                trailer.push_str(&handlers::warnings::format_count(set.execution_info.warning_count));
+            }
+            if self.warnings.enabled && complete && index + 1 == set_count {
+                trailer.push_str(&self.fetch_warnings()?);
+            }
+            self.apply_replacements(&mut trailer);
+            formatted_result.push_str(&trailer);
+        }
 
-                    if self.args.record {
-                        write!(self.output_buffer, "{}", formatted_result)?;
-                    } else {
-                        if let Err(e) = self.compare_with_result(&formatted_result) {
-                            // Clear current query info on error
-                            self.clear_current_query();
-                            return Err(e);
-                        }
-                    }
-                }
+        if self.args.record {
+            write!(self.output_buffer, "{}", formatted_result)?;
+            Ok(())
+        } else {
+            self.compare_with_result(&formatted_result)
+        }
+    }
 
-                // Clear current query info on success
-                self.clear_current_query();
-            }
-            Err(e) => {
-                let error_handled = self.handle_query_error(&e)?;
-                if !error_handled {
-                    // Clear current query info on error
-                    self.clear_current_query();
-                    return Err(e);
-                }
-                // Clear current query info after handling error
-                self.clear_current_query();
+    /// Check the error of a failed statement against the expected errors
+    fn check_query_error(&mut self, error: anyhow::Error) -> Result<()> {
+        if self.handle_query_error(&error)? {
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+
+    /// Refresh $mysql_errno, $mysql_errname, $mysql_sqlstate, $mysql_affected_rows
+    /// and $mysql_insert_id from the outcome of the last statement
+    fn update_status_variables(&mut self, execution_result: &Result<QueryResult>) {
+        let (errno, sqlstate, affected_rows, insert_id) = match execution_result {
+            Ok(result @ QueryResult { error: None, .. }) => {
+                // After a multi-statement query these describe its last statement
+                let last = result.result_sets().last().unwrap_or(result);
+                (
+                    0,
+                    "00000".to_string(),
+                    last.execution_info.affected_rows.to_string(),
+                    last.execution_info.last_insert_id,
+                )
+            }
+            Ok(QueryResult { error: Some(e), .. }) | Err(e) => {
+                let (errno, sqlstate) = match e.downcast_ref::<mysql::Error>() {
+                    Some(mysql_error) => (
+                        self.error_handler.get_error_code(mysql_error),
+                        self.error_handler.get_sqlstate(mysql_error).to_string(),
+                    ),
+                    None => (None, "HY000".to_string()),
+                };
+                let errno = errno
+                    .or_else(|| error_codes::error_code("CR_UNKNOWN_ERROR"))
+                    .unwrap_or_default();
+                // mysql_affected_rows() reports -1 after a failed statement
+                (errno, sqlstate, "-1".to_string(), 0)
             }
+        };
+        let errname = match errno {
+            0 => "",
+            code => error_codes::error_name(code).unwrap_or(""),
+        };
+
+        self.variable_context.set("mysql_errno", errno.to_string());
+        self.variable_context.set("mysql_errname", errname);
+        self.variable_context.set("mysql_sqlstate", sqlstate);
+        self.variable_context.set("mysql_affected_rows", affected_rows);
+        self.variable_context.set("mysql_insert_id", insert_id.to_string());
+    }
+
+    /// Run SHOW WARNINGS on the current connection, which is the one that ran
+    /// the previous statement, and render the warnings that are not suppressed
+    fn fetch_warnings(&mut self) -> Result<String> {
+        let result = self
+            .connection_manager
+            .current_database()?
+            .query_with_metadata(SHOW_WARNINGS)?;
+        Ok(self.warnings.format(&result.rows))
+    }
+
+    /// Run the statement following `--require <file>`. Its output is compared
+    /// with the file instead of the result file; a mismatch skips the test.
+    fn execute_require_query(&mut self, sql: &str, require_file: &Path) -> Result<()> {
+        let require_path = if require_file.is_absolute() {
+            require_file.to_path_buf()
+        } else {
+            self.current_dir.join(require_file)
+        };
+        let expected = fs::read(&require_path)
+            .map(|bytes| self.file_charset.decode(&bytes).into_owned())
+            .map_err(|e| {
+                anyhow!(
+                    "Failed to read require file {}: {}",
+                    require_path.display(),
+                    e
+                )
+            })?;
+
+        let expanded_sql = self.variable_context.expand(sql)?;
+        let result = self
+            .connection_manager
+            .current_database()?
+            .query_with_metadata(&expanded_sql)?;
+        let actual = self.format_query_result_to_string(&result.columns, &result.rows)?;
+
+        if actual.trim_end() != expected.trim_end() {
+            let reason = format!("Test requires: '{}'", require_file.display());
+            debug!("Require check failed, actual output: {:?}", actual);
+            self.stop_request = Some(StopRequest::Skip(reason));
         }
         Ok(())
     }
 
     /// Format query results to a string
-    fn format_query_result_to_string(&self, rows: &[Vec<String>]) -> Result<String> {
+    fn format_query_result_to_string(&self, columns: &[ColumnMeta], rows: &[Vec<String>]) -> Result<String> {
+        let vertical = self.vertical_results || self.pending_vertical;
+        let mut result = String::new();
+        let column_names: Vec<String> = columns
+            .iter()
+            .map(|c| {
+                let mut name = c.name.clone();
+                self.apply_replacements(&mut name);
+                name
+            })
+            .collect();
+
+        // mysqltest 格式：每个结果集一行列名（纵向输出时列名已在每行中）
+        if self.display_column_names && !vertical && !columns.is_empty() {
+            result.push_str(&column_names.join("\t"));
+            result.push('\n');
+        }
+
         if rows.is_empty() {
-            return Ok(String::new());
+            return Ok(result);
         }
 
-        let mut result = String::new();
         let mut sorted_rows = rows.to_vec();
 
+        // JSON 单元格先规范化，再做列替换与排序
+        if let Some(json) = self.pending_json_result.or(self.json_result) {
+            for row in sorted_rows.iter_mut() {
+                handlers::json_result::apply_to_row(row, columns, json);
+            }
+        }
+
+        // mysqltest 语义：先替换列，再逐个单元格应用 replace_regex/replace_result，最后排序
+        if !self.pending_replace_column.is_empty() {
+            for row in sorted_rows.iter_mut() {
+                handlers::replace_column::apply_to_row(row, &self.pending_replace_column);
+            }
+        }
+        for cell in sorted_rows.iter_mut().flatten() {
+            self.apply_replacements(cell);
+        }
+
         if self.pending_sorted_result {
             sorted_rows.sort();
         }
 
+        if vertical {
+            return Ok(handlers::vertical_results::format_vertical(&column_names, &sorted_rows));
+        }
+
         for row in sorted_rows {
             result.push_str(&row.join("\t"));
             result.push('\n');
@@ -885,7 +1767,7 @@ impl Tester {
                         } else {
                             "Got one of the listed errors\n".to_string()
                         };
-                    self.apply_regex_replacements(&mut error_output);
+                    self.apply_replacements(&mut error_output);
 
                     if self.args.record {
                         write!(self.output_buffer, "{}", error_output)?;
@@ -932,10 +1814,9 @@ impl Tester {
     // }
 
     /// Parses a --error command
-    fn parse_expected_errors(&mut self, error_spec: &str) -> Result<()> {
-        self.expected_errors = error_spec
-            .split(',')
-            .map(|s| s.trim().to_string())
+    fn parse_expected_errors(&mut self, error_spec: &str, line: usize) -> Result<()> {
+        self.expected_errors = handlers::error::parse_expected_errors(error_spec, line)?
+            .into_iter()
             .collect();
         debug!("Expected errors set to: {:?}", self.expected_errors);
         Ok(())
@@ -1026,8 +1907,8 @@ impl Tester {
         }
 
         // Read the source file content
-        let source_content = match fs::read_to_string(&source_file_path) {
-            Ok(content) => content,
+        let source_content = match charset::read_text_file(&source_file_path, Some(self.file_charset)) {
+            Ok((content, _)) => content,
             Err(e) => {
                 let error = anyhow!(
                     "Failed to read source file {}: {} at line {}",
@@ -1090,6 +1971,9 @@ impl Tester {
 
         // Execute the source file queries
         for (index, source_query) in source_queries.iter().enumerate() {
+            if self.stop_request.is_some() {
+                break;
+            }
             if let Err(e) = self.execute_query(source_query, index) {
                 // Decrement depth on error and propagate
                 self.source_depth -= 1;
@@ -1148,11 +2032,11 @@ impl Tester {
         Ok(())
     }
 
-    /// Applies stored regex replacements to a string buffer
-    pub fn apply_regex_replacements(&self, buffer: &mut String) {
+    /// Applies stored --replace_regex and then --replace_result rules to a string buffer
+    pub fn apply_replacements(&self, buffer: &mut String) {
         use std::borrow::Cow;
 
-        if self.pending_replace_regex.is_empty() {
+        if self.pending_replace_regex.is_empty() && self.pending_replace_result.is_empty() {
             return;
         }
 
@@ -1165,6 +2049,10 @@ impl Tester {
             }
         }
 
+        if let Some(s) = handlers::replace_result::apply(&cow, &self.pending_replace_result) {
+            cow = Cow::Owned(s);
+        }
+
         if let Cow::Owned(s) = cow {
             *buffer = s;
         }
@@ -1181,7 +2069,8 @@ impl Tester {
         let result_file = result_dir.join(format!("{}.{}", test_name, self.args.extension));
 
         if result_file.exists() {
-            self.result_file_content = Some(fs::read_to_string(result_file)?);
+            let bytes = fs::read(result_file)?;
+            self.result_file_content = Some(self.file_charset.decode(&bytes).into_owned());
             debug!("Loaded result file for comparison: {}", test_name);
         } else {
             warn!("Result file not found for test: {}", test_name);
@@ -1193,48 +2082,111 @@ impl Tester {
 
     /// Compare current output with expected result
     pub fn compare_with_result(&mut self, output: &str) -> Result<()> {
+        // The actual output is kept in both modes: it becomes the result file
+        // when recording and the .reject file when it differs
+        self.output_buffer.write_all(output.as_bytes())?;
         if self.args.record {
-            self.output_buffer.write_all(output.as_bytes())?;
             return Ok(());
         }
 
-        if let Some(content) = &self.result_file_content {
-            let expected_lines: Vec<&str> = content.lines().collect();
-            let actual_lines = output.lines();
-
-            for actual_line in actual_lines {
-                let cursor = self.current_result_line - 1;
-
-                if cursor >= expected_lines.len() {
-                    let err_msg = format!(
-                        "Output has more lines than expected. Extra line: '{}'",
-                        actual_line
-                    );
-                    return Err(anyhow!(err_msg));
+        let Some(content) = &self.result_file_content else {
+            if !output.is_empty() {
+                return Err(anyhow!("Result file not found, but output was produced."));
+            }
+            return Ok(());
+        };
+        let expected_lines: Vec<&str> = content.lines().collect();
+        let actual_lines: Vec<&str> = output.lines().collect();
+        let mut cursor = self.current_result_line - 1;
+        let mut options = self
+            .pending_compare
+            .clone()
+            .unwrap_or_else(|| self.compare_options.clone());
+        // JSON cells written by --json_result are compared structurally
+        options.json |= self.pending_json_result.is_some() || self.json_result.is_some();
+
+        // After a difference the output is only recorded. With
+        // --continue-on-mismatch, comparison resumes at the next command whose
+        // output is found further down in the result file.
+        if self.result_mismatch {
+            if !self.args.continue_on_mismatch || self.command_counter == self.mismatch_command {
+                return Ok(());
+            }
+            match find_resync_point(&expected_lines, cursor, &actual_lines, &options) {
+                Some(index) => {
+                    debug!("Result comparison resynchronised at result line {}", index + 1);
+                    cursor = index;
+                    self.result_mismatch = false;
                 }
+                None => return Ok(()),
+            }
+        }
 
-                let expected_line = expected_lines[cursor];
-                if actual_line != expected_line {
+        let mut mismatch = None;
+        for actual_line in &actual_lines {
+            match expected_lines.get(cursor) {
+                None => {
+                    mismatch = Some((
+                        format!(
+                            "Output has more lines than expected. Extra line: '{}'",
+                            actual_line
+                        ),
+                        String::new(),
+                        actual_line.to_string(),
+                    ));
+                    break;
+                }
+                Some(expected_line) => {
+                    let Err(reason) = options.compare_line(expected_line, actual_line) else {
+                        cursor += 1;
+                        continue;
+                    };
                     let test_file_info = if self.current_query_line > 0 {
                         format!(" (from test file line {})", self.current_query_line)
                     } else {
                         String::new()
                     };
-
-                    let err_msg = format!(
-                        "Output mismatch at result line {}{}:\n    Expected: {}\n    Actual: {}",
-                        self.current_result_line, test_file_info, expected_line, actual_line
-                    );
-                    return Err(anyhow!(err_msg));
+                    // With comparison options, say which cell failed and why
+                    let reason = if options.is_exact() {
+                        String::new()
+                    } else {
+                        format!("\n    Reason: {}", reason)
+                    };
+                    mismatch = Some((
+                        format!(
+                            "Output mismatch at result line {}{}:\n    Expected: {}\n    Actual: {}{}",
+                            cursor + 1,
+                            test_file_info,
+                            expected_line,
+                            actual_line,
+                            reason
+                        ),
+                        expected_line.to_string(),
+                        actual_line.to_string(),
+                    ));
+                    break;
                 }
-
-                self.current_result_line += 1;
-            }
-        } else {
-            if !output.is_empty() {
-                return Err(anyhow!("Result file not found, but output was produced."));
             }
         }
+        self.current_result_line = cursor + 1;
+
+        let Some((err_msg, expected, actual)) = mismatch else {
+            return Ok(());
+        };
+        self.result_mismatch = true;
+        self.mismatch_command = self.command_counter;
+        if !self.args.continue_on_mismatch {
+            return Err(anyhow!(err_msg));
+        }
+
+        // Keep going; every mismatch is reported when the test ends
+        self.mismatch_failures.push(QueryFailureDetail {
+            sql: self.current_query.clone().unwrap_or_default(),
+            expected,
+            actual,
+            line_number: self.current_query_line,
+            error_message: err_msg,
+        });
         Ok(())
     }
 
@@ -1272,21 +2224,122 @@ impl Tester {
         } else {
             self.current_dir.join(result_dir)
         }
-    }
+    }
+
+    /// Write result file
+    fn write_result_file(&self, test_name: &str) -> Result<()> {
+        let result_dir = self.get_result_dir_path();
+        fs::create_dir_all(&result_dir)?;
+
+        // 若 test_name 包含路径分隔符，需要提前创建子目录
+        let result_file = result_dir.join(format!("{}.{}", test_name, self.args.extension));
+        if let Some(parent) = result_file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Markers of the previous result file are kept where the new output
+        // still matches them
+        let output = String::from_utf8_lossy(&self.output_buffer);
+        let content = match fs::read(&result_file) {
+            Ok(previous) => result_pattern::preserve_markers(&self.file_charset.decode(&previous), &output),
+            Err(_) => output.into_owned(),
+        };
+        fs::write(result_file, self.file_charset.encode(&content))?;
+
+        info!("Result file written for test: {}", test_name);
+        Ok(())
+    }
+
+    /// Write `<test>.reject` next to the result file when the actual output
+    /// differs from it and return the unified diff, capped at --max-diff-lines.
+    /// A reject file left by an earlier failing run is removed.
+    fn write_reject_file(&self, test_name: &str) -> Result<Option<String>> {
+        let Some(expected) = &self.result_file_content else {
+            return Ok(None);
+        };
+        let result_file = self
+            .get_result_dir_path()
+            .join(format!("{}.{}", test_name, self.args.extension));
+        let reject_file = result_file.with_extension("reject");
+        let display = |path: &Path| {
+            path.strip_prefix(&self.current_dir)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+
+        let actual = String::from_utf8_lossy(&self.output_buffer);
+        // Lines matched by a marker are not reported as differences
+        let diff = diff::unified_diff(
+            expected,
+            &result_pattern::preserve_markers(expected, &actual),
+            &display(&result_file),
+            &display(&reject_file),
+            3,
+        );
+        if diff.is_empty() {
+            if reject_file.exists() {
+                fs::remove_file(&reject_file)?;
+            }
+            return Ok(None);
+        }
 
-    /// Write result file
-    fn write_result_file(&self, test_name: &str) -> Result<()> {
-        let result_dir = self.get_result_dir_path();
-        fs::create_dir_all(&result_dir)?;
+        fs::write(&reject_file, self.file_charset.encode(&actual))?;
+        info!("Reject file written: {}", reject_file.display());
+        Ok(Some(diff::truncate_lines(&diff, self.args.max_diff_lines)))
+    }
 
-        // 若 test_name 包含路径分隔符，需要提前创建子目录
-        let result_file = result_dir.join(format!("{}.{}", test_name, self.args.extension));
-        if let Some(parent) = result_file.parent() {
-            fs::create_dir_all(parent)?;
+    /// --record-pending writes changed output to `<result>.new`; --review asks
+    /// for each changed hunk and writes the accepted ones to the result file
+    fn review_result_changes(&self, test_name: &str) -> Result<()> {
+        if !self.args.review && !self.args.record_pending {
+            return Ok(());
+        }
+        let result_file = self
+            .get_result_dir_path()
+            .join(format!("{}.{}", test_name, self.args.extension));
+        let expected = self.result_file_content.as_deref().unwrap_or("");
+        let actual = result_pattern::preserve_markers(expected, &String::from_utf8_lossy(&self.output_buffer));
+        let unchanged = self.result_file_content.is_some() && expected.lines().eq(actual.lines());
+
+        if self.args.record_pending {
+            let pending_file = PathBuf::from(format!("{}.new", result_file.display()));
+            if unchanged {
+                if pending_file.exists() {
+                    fs::remove_file(&pending_file)?;
+                }
+            } else {
+                if let Some(parent) = pending_file.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&pending_file, self.file_charset.encode(&actual))?;
+                info!("Pending result written: {}", pending_file.display());
+            }
+            return Ok(());
         }
-        fs::write(result_file, &self.output_buffer)?;
 
-        info!("Result file written for test: {}", test_name);
+        if unchanged {
+            return Ok(());
+        }
+        let name = result_file
+            .strip_prefix(&self.current_dir)
+            .unwrap_or(&result_file)
+            .display()
+            .to_string();
+        let stdin = std::io::stdin();
+        let merged = review::review_changes(
+            &name,
+            expected,
+            &actual,
+            &mut stdin.lock(),
+            &mut std::io::stdout(),
+        )?;
+        if let Some(merged) = merged {
+            if let Some(parent) = result_file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&result_file, self.file_charset.encode(&merged))?;
+            info!("Result file updated: {}", result_file.display());
+        }
         Ok(())
     }
 
@@ -1394,6 +2447,37 @@ impl Tester {
     }
 }
 
+/// Connection parameters from --params, with the --character-set default added
+fn connection_params(args: &Args) -> String {
+    match args.character_set {
+        Some(charset) if args.params.is_empty() => format!("charset={}", charset.name()),
+        Some(charset) => format!("{}&charset={}", args.params, charset.name()),
+        None => args.params.clone(),
+    }
+}
+
+/// Where the output of the next command starts in the result file after a
+/// difference: the first position at or after `cursor` where all its lines match.
+/// The search is greedy, so output that also appears earlier than its real
+/// position resynchronises at the earlier copy.
+fn find_resync_point(
+    expected: &[&str],
+    cursor: usize,
+    actual: &[&str],
+    options: &CompareOptions,
+) -> Option<usize> {
+    if actual.is_empty() {
+        return None;
+    }
+    (cursor..expected.len()).find(|&start| {
+        expected.len() - start >= actual.len()
+            && expected[start..start + actual.len()]
+                .iter()
+                .zip(actual)
+                .all(|(e, a)| options.compare_line(e, a).is_ok())
+    })
+}
+
 /// Test execution result
 #[derive(Debug, Clone)]
 pub struct TestResult {
@@ -1418,6 +2502,10 @@ pub struct TestResult {
     pub start_time: String,
     /// Test end timestamp in ISO 8601 format  
     pub end_time: String,
+    /// Why the test was skipped (--skip, failed --require probes)
+    pub skip_reason: Option<String>,
+    /// Unified diff between the result file and the actual output (.reject)
+    pub diff: Option<String>,
 }
 
 /// Detailed information about a query failure
@@ -1458,9 +2546,18 @@ impl TestResult {
             query_failures: Vec::new(),
             start_time: String::new(),
             end_time: String::new(),
+            skip_reason: None,
+            diff: None,
         }
     }
 
+    /// Mark test as skipped with the given reason
+    pub fn mark_skipped(&mut self, reason: String) {
+        self.success = true;
+        self.status = TestStatus::Skipped;
+        self.skip_reason = Some(reason);
+    }
+
     /// Mark test as failed
     pub fn mark_failed(&mut self) {
         self.success = false;
@@ -1506,15 +2603,34 @@ impl TestResult {
 
 impl Tester {
     /// Build control flow mapping for if/while/end structures
+    ///
+    /// `if` and each `elseif`/`else` branch map to the next branch of the chain
+    /// (or the closing `end`/`}`); the closing statement maps back to the block start.
     fn build_control_flow_map(&mut self, queries: &[Query]) -> Result<()> {
-        let mut stack = Vec::new();
+        // (block start, last branch of the if chain)
+        let mut stack: Vec<(usize, usize)> = Vec::new();
 
         for (i, query) in queries.iter().enumerate() {
             match query.query_type {
-                QueryType::If | QueryType::While => stack.push(i),
-                QueryType::End => {
-                    if let Some(start_index) = stack.pop() {
-                        self.control_flow_map.insert(start_index, i);
+                QueryType::If | QueryType::While | QueryType::Foreach => stack.push((i, i)),
+                QueryType::ElseIf | QueryType::Else => match stack.last_mut() {
+                    Some((start_index, last_branch))
+                        if queries[*start_index].query_type == QueryType::If =>
+                    {
+                        self.control_flow_map.insert(*last_branch, i);
+                        *last_branch = i;
+                    }
+                    _ => {
+                        return Err(anyhow!(
+                            "Mismatched '{}' at line {}",
+                            if query.query_type == QueryType::Else { "else" } else { "elseif" },
+                            query.line
+                        ));
+                    }
+                },
+                QueryType::End | QueryType::CloseBrace => {
+                    if let Some((start_index, last_branch)) = stack.pop() {
+                        self.control_flow_map.insert(last_branch, i);
                         self.control_flow_map.insert(i, start_index);
                     } else {
                         return Err(anyhow!("Mismatched 'end' at line {}", query.line));
@@ -1524,7 +2640,7 @@ impl Tester {
             }
         }
 
-        if let Some(&unclosed_idx) = stack.last() {
+        if let Some(&(unclosed_idx, _)) = stack.last() {
             let unclosed_query = &queries[unclosed_idx];
             return Err(anyhow!(
                 "Unclosed control block starting at line {}",
@@ -1541,8 +2657,12 @@ impl Tester {
         &mut self,
         query: &Query,
         pc: usize,
-        _queries: &[Query],
+        queries: &[Query],
     ) -> Result<usize> {
+        if query.query_type == QueryType::Connection {
+            self.check_switch_to_pending_connection(query, pc, queries)?;
+        }
+
         // Handle concurrent blocks first
         if query.query_type == QueryType::BeginConcurrent {
             self.in_concurrent_block = true;
@@ -1564,8 +2684,13 @@ impl Tester {
 
         if self.in_concurrent_block {
             match query.query_type {
-                QueryType::Query => {
+                QueryType::Query | QueryType::QueryVertical => {
                     let mut concurrent_query = query.clone();
+                    if query.query_type == QueryType::QueryVertical {
+                        concurrent_query.query_type = QueryType::Query;
+                        concurrent_query.query =
+                            query.query.trim_end().trim_end_matches(';').to_string();
+                    }
                     // Expand variables in the SQL query before storing
                     concurrent_query.query =
                         self.variable_context.expand(&concurrent_query.query)?;
@@ -1581,16 +2706,38 @@ impl Tester {
                         self.pending_replace_regex.clear();
                     }
 
+                    if !self.pending_replace_column.is_empty() {
+                        concurrent_query.options.replace_column =
+                            std::mem::take(&mut self.pending_replace_column);
+                    }
+
+                    if !self.pending_replace_result.is_empty() {
+                        concurrent_query.options.replace_result =
+                            std::mem::take(&mut self.pending_replace_result);
+                    }
+
                     if self.pending_sorted_result {
                         concurrent_query.options.sorted_result = true;
                         self.pending_sorted_result = false;
                     }
 
+                    if self.pending_compare.is_some() {
+                        concurrent_query.options.compare = self.pending_compare.take();
+                    }
+
+                    if self.pending_json_result.is_some() {
+                        concurrent_query.options.json_result = self.pending_json_result.take();
+                    }
+
+                    // 纵向输出取决于语句本身或当时的 --vertical_results
+                    concurrent_query.options.vertical =
+                        query.query_type == QueryType::QueryVertical || self.vertical_results;
+
                     self.concurrent_queries.push(concurrent_query);
                 }
                 QueryType::Error => {
                     // Store error expectations for the next query
-                    self.parse_expected_errors(&query.query)?;
+                    self.parse_expected_errors(&query.query, query.line)?;
                 }
                 _ => {
                     // Execute other commands immediately in serial.
@@ -1602,9 +2749,13 @@ impl Tester {
 
         // Handle control flow: if, while, end
         let next_pc = match query.query_type {
-            QueryType::If => self.handle_if_command(&query.query, pc)?,
-            QueryType::While => self.handle_while_command(&query.query, pc)?,
-            QueryType::End => self.handle_end_command(pc)?,
+            QueryType::If => self.handle_if_command(&query.query, pc, queries)?,
+            QueryType::ElseIf | QueryType::Else => self.handle_else_command(pc, queries)?,
+            QueryType::While => self.handle_while_command(&query.query, pc, query.line)?,
+            QueryType::Foreach => self.handle_foreach_command(&query.query, pc, query.line)?,
+            QueryType::End | QueryType::CloseBrace => self.handle_end_command(pc)?,
+            QueryType::Break => self.handle_break_command(query.line)?,
+            QueryType::Continue => self.handle_continue_command(query.line)?,
             _ => {
                 // Execute the query normally
                 self.execute_query(query, pc)?;
@@ -1615,28 +2766,50 @@ impl Tester {
         Ok(next_pc)
     }
 
-    /// Handle if command
-    fn handle_if_command(&mut self, condition: &str, pc: usize) -> Result<usize> {
-        // Evaluate the condition
-        if self.expression_evaluator.evaluate_condition(
-            condition,
-            &self.variable_context,
-            self.connection_manager.current_database()?,
-        )? {
-            // Condition is true, continue to the next statement
-            Ok(pc + 1)
-        } else {
-            // Condition is false, jump to matching end
-            let end_index = *self
-                .control_flow_map
-                .get(&pc)
-                .ok_or_else(|| anyhow!("Mismatched 'end' for 'if' at line {}", pc))?;
-            Ok(end_index + 1)
+    /// Handle if command, trying the elseif/else branches in order
+    fn handle_if_command(&mut self, condition: &str, pc: usize, queries: &[Query]) -> Result<usize> {
+        let mut branch = pc;
+        let mut condition = condition;
+
+        loop {
+            // Evaluate the condition
+            if self.expression_evaluator.evaluate_condition(
+                condition,
+                &self.variable_context,
+                self.connection_manager.current_database()?,
+            )? {
+                // Condition is true, continue to the first statement of this branch
+                return Ok(branch + 1);
+            }
+
+            // Condition is false, jump to the next branch or the matching end
+            let next = *self.control_flow_map.get(&branch).ok_or_else(|| {
+                anyhow!("Mismatched 'end' for 'if' at line {}", queries[branch].line)
+            })?;
+            match queries[next].query_type {
+                QueryType::ElseIf => {
+                    branch = next;
+                    condition = &queries[next].query;
+                }
+                // else 分支直接进入；end / } 则跳出
+                _ => return Ok(next + 1),
+            }
+        }
+    }
+
+    /// Reaching `else`/`elseif` means the previous branch ran: skip to the end of the chain
+    fn handle_else_command(&self, pc: usize, queries: &[Query]) -> Result<usize> {
+        let mut index = pc;
+        while matches!(queries[index].query_type, QueryType::ElseIf | QueryType::Else) {
+            index = *self.control_flow_map.get(&index).ok_or_else(|| {
+                anyhow!("Mismatched 'end' for branch at line {}", queries[index].line)
+            })?;
         }
+        Ok(index + 1)
     }
 
     /// Handle while command
-    fn handle_while_command(&mut self, condition: &str, pc: usize) -> Result<usize> {
+    fn handle_while_command(&mut self, condition: &str, pc: usize, line: usize) -> Result<usize> {
         let end_index = *self
             .control_flow_map
             .get(&pc)
@@ -1650,9 +2823,11 @@ impl Tester {
             // Condition is true, push to stack and continue
             self.while_stack.push(WhileFrame {
                 start_index: pc,
+                line,
                 end_index,
                 condition: condition.to_string(),
                 iteration_count: 0,
+                foreach: None,
             });
 
             Ok(pc + 1) // Continue to first statement in loop
@@ -1662,26 +2837,81 @@ impl Tester {
         }
     }
 
+    /// Handle foreach command: capture the values once, bind the first one and enter the body
+    fn handle_foreach_command(&mut self, spec: &str, pc: usize, line: usize) -> Result<usize> {
+        let end_index = *self
+            .control_flow_map
+            .get(&pc)
+            .ok_or_else(|| anyhow!("Mismatched 'end' for 'foreach' at line {}", line))?;
+
+        let spec = handlers::foreach::parse_foreach(spec)?;
+        let mut iteration = match spec.source {
+            ForeachSource::List(items) => {
+                let expanded = self.variable_context.expand(&items)?;
+                ForeachIteration::from_list(&spec.var, handlers::foreach::split_list(&expanded)?)
+            }
+            ForeachSource::Query(sql) => {
+                let expanded = self.variable_context.expand(&sql)?;
+                let result = self
+                    .connection_manager
+                    .current_database()?
+                    .query_with_metadata(&expanded)?;
+                ForeachIteration::from_rows(&spec.var, result.column_names(), result.rows)
+            }
+        };
+
+        if !iteration.bind_next(&mut self.variable_context) {
+            // Nothing to iterate, skip the loop
+            return Ok(end_index + 1);
+        }
+
+        self.while_stack.push(WhileFrame {
+            start_index: pc,
+            line,
+            end_index,
+            condition: String::new(),
+            iteration_count: 0,
+            foreach: Some(iteration),
+        });
+        Ok(pc + 1)
+    }
+
     /// Handle end command
     fn handle_end_command(&mut self, pc: usize) -> Result<usize> {
         if let Some(frame) = self.while_stack.last_mut() {
             if frame.end_index == pc {
+                if let Some(iteration) = frame.foreach.as_mut() {
+                    // This 'end' closes an active foreach loop: bind the next value or leave
+                    if iteration.bind_next(&mut self.variable_context) {
+                        return Ok(frame.start_index + 1);
+                    }
+                    self.while_stack.pop();
+                    return Ok(pc + 1);
+                }
+
                 // This 'end' matches an active while loop
                 frame.iteration_count += 1;
-                if frame.iteration_count >= MAX_LOOP_ITERATIONS {
-                    return Err(anyhow!(
-                        "Infinite loop detected at line {}",
-                        frame.start_index + 1
-                    ));
-                }
 
                 if self.expression_evaluator.evaluate_condition(
                     &frame.condition,
                     &self.variable_context,
                     self.connection_manager.current_database()?,
                 )? {
-                    // Loop condition is still true, jump back to while
-                    return Ok(frame.start_index);
+                    if frame.iteration_count >= self.max_loop_iterations {
+                        // Leave the loop, so a test going on after the failure
+                        // does not take its frame for the enclosing block's
+                        let line = frame.line;
+                        self.while_stack.pop();
+                        return Err(anyhow!(
+                            "Loop at line {} exceeded the maximum of {} iterations (variables: {})",
+                            line,
+                            self.max_loop_iterations,
+                            self.describe_variables()
+                        ));
+                    }
+                    // Loop condition is still true, jump back to the first statement in the loop
+                    // (re-entering the while command would push a new frame and reset the count)
+                    return Ok(frame.start_index + 1);
                 } else {
                     // Loop condition is false, pop from stack and continue
                     self.while_stack.pop();
@@ -1694,13 +2924,48 @@ impl Tester {
         Ok(pc + 1)
     }
 
+    /// Handle break: leave the innermost active while loop
+    fn handle_break_command(&mut self, line: usize) -> Result<usize> {
+        let frame = self
+            .while_stack
+            .pop()
+            .ok_or_else(|| anyhow!("'break' outside of a 'while' loop at line {}", line))?;
+        Ok(frame.end_index + 1)
+    }
+
+    /// Handle continue: jump to the 'end' of the innermost active while loop,
+    /// which counts the iteration and re-evaluates the condition
+    fn handle_continue_command(&mut self, line: usize) -> Result<usize> {
+        let frame = self
+            .while_stack
+            .last()
+            .ok_or_else(|| anyhow!("'continue' outside of a 'while' loop at line {}", line))?;
+        Ok(frame.end_index)
+    }
+
+    /// Current variable values, sorted by name, for loop diagnostics
+    fn describe_variables(&self) -> String {
+        let mut names: Vec<&String> = self.variable_context.keys().collect();
+        if names.is_empty() {
+            return "none".to_string();
+        }
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| {
+                let value = self.variable_context.get(name).map(String::as_str).unwrap_or("");
+                format!("${} = {}", name, value)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     fn execute_concurrent_queries(&mut self) -> Result<()> {
         if self.concurrent_queries.is_empty() {
             return Ok(());
         }
 
-        // 注意：并发块执行查询但不输出到 result 文件
-        // 这是有意设计的 - 并发查询只是执行，不记录输出
+        // 并发块的输出（结果行、告警、预期错误）按语句原始顺序写入 result 文件
 
         let indexed_queries: Vec<_> = self
             .concurrent_queries
@@ -1708,17 +2973,20 @@ impl Tester {
             .cloned()
             .enumerate()
             .collect();
-        let results = Arc::new(Mutex::new(Vec::<(
-            usize,
-            Result<String, mysql::Error>,
-            Vec<String>,
-        )>::new()));
+        let results = Arc::new(Mutex::new(Vec::<ConcurrentResult>::new()));
+        // 工作连接沿用当前连接的字符集
+        let database = self.connection_manager.current_database()?;
+        let charset = database.charset();
+        // 每个工作连接各自受语句超时约束，超时后由看门狗 KILL QUERY
+        let pool = database.pool();
+        let timeout = watchdog::statement_timeout(self.query_timeout, self.test_deadline);
 
         indexed_queries.par_iter().for_each(|(index, query)| {
             // 尝试获取连接，若失败则将错误入结果集合并，不直接 panic
             let conn_result = self.connection_manager.get_pooled_connection();
 
-            let query_result: Result<String, mysql::Error> = match conn_result {
+            let mut hang = None;
+            let query_result: Result<ConcurrentOutput, mysql::Error> = match conn_result {
                 Err(_e) => {
                     // 将连接错误转为 DriverError::CouldNotConnect(None)
                     Err(mysql::Error::DriverError(
@@ -1728,122 +2996,187 @@ impl Tester {
                 Ok(mut conn) => {
                     // 并发路径下，查询字符串已不包含错误前缀
                     let actual_query = query.query.clone();
+                    let watchdog =
+                        timeout.map(|timeout| Watchdog::arm(pool.clone(), conn.connection_id(), timeout));
 
-                    // 执行查询
-                    conn.query_iter(&actual_query).and_then(|result| {
-                        let rows: Vec<String> = result
+                    // 执行查询；单元格在此格式化，结果集的输出格式由主线程按串行路径生成
+                    let session = if charset.is_utf8() {
+                        Ok(())
+                    } else {
+                        conn.query_drop(charset.session_statement())
+                    };
+                    let output = session.and_then(|_| conn.query_iter(&actual_query)).and_then(|result| {
+                        let columns: Vec<ColumnMeta> =
+                            result.columns().as_ref().iter().map(ColumnMeta::from).collect();
+                        let rows = result
                             .map(|row_result| {
                                 let row = row_result?;
-                                let row_values: Vec<String> = (0..row.len())
-                                    .map(|i| {
-                                        let value = row
-                                            .get::<Option<String>, _>(i)
-                                            .unwrap_or_else(|| Some("NULL".to_string()))
-                                            .unwrap_or_else(|| "NULL".to_string());
-                                        value
+                                Ok(row
+                                    .columns_ref()
+                                    .iter()
+                                    .enumerate()
+                                    .map(|(i, column)| {
+                                        let value = row.as_ref(i).unwrap_or(&mysql::Value::NULL);
+                                        self.value_format.format(value, column, charset)
                                     })
-                                    .collect();
-                                Ok(row_values.join("\t"))
+                                    .collect())
                             })
-                            .collect::<Result<Vec<String>, mysql::Error>>()?;
-                        let mut output = rows.join("\n");
-                        // 应用一次性替换规则（仅对该 Query 生效）
-                        for (regex, replacement) in &query.options.replace_regex {
-                            output = regex
-                                .replace_all(&output, replacement.as_str())
-                                .into_owned();
-                        }
-                        if query.options.sorted_result {
-                            // 若要求排序，按照行排序
-                            let mut lines: Vec<&str> = output.lines().collect();
-                            lines.sort();
-                            output = lines.join("\n");
-                        }
-                        Ok(output)
+                            .collect::<Result<Vec<Vec<String>>, mysql::Error>>()?;
+                        Ok((columns, rows))
+                    });
+
+                    hang = watchdog.and_then(Watchdog::disarm);
+
+                    // 告警在执行该语句的工作连接上获取
+                    output.and_then(|(columns, rows)| {
+                        let warnings = if self.warnings.enabled {
+                            conn.query_map(SHOW_WARNINGS, |(level, code, message): (String, String, String)| {
+                                vec![level, code, message]
+                            })?
+                        } else {
+                            Vec::new()
+                        };
+                        Ok((columns, rows, warnings))
                     })
                 }
             };
 
-            let expected_errors: Vec<String> = query.options.expected_errors.clone();
-
             // 若 Mutex 被 poison，into_inner 仍可安全取得数据；仅记录告警日志
             match results.lock() {
-                Ok(mut guard) => guard.push((*index, query_result, expected_errors)),
+                Ok(mut guard) => guard.push((*index, query_result, hang)),
                 Err(poisoned) => {
                     warn!("Results mutex poisoned, continuing with inner data");
                     let mut guard = poisoned.into_inner();
-                    guard.push((*index, query_result, expected_errors));
+                    guard.push((*index, query_result, hang));
                 }
             }
         });
 
         let mut final_results = match results.lock() {
-            Ok(guard) => guard,
+            Ok(mut guard) => std::mem::take(&mut *guard),
             Err(poisoned) => {
                 warn!("Results mutex poisoned during collection; using inner data");
-                poisoned.into_inner()
+                std::mem::take(&mut *poisoned.into_inner())
             }
         };
         final_results.sort_by_key(|(index, _, _)| *index);
 
-        let mut output_parts: Vec<String> = Vec::new();
-        for (_, result, expected_errors) in final_results.iter() {
-            match result {
-                Ok(output) => {
+        // 每条语句已记录捕获时的纵向模式，写出期间不再叠加当前的 --vertical_results
+        let vertical_results = std::mem::take(&mut self.vertical_results);
+        let written = self.write_concurrent_results(final_results);
+        self.vertical_results = vertical_results;
+
+        self.in_concurrent_block = false;
+        self.concurrent_queries.clear();
+        // 并发块结束后，清理一次性修饰符，避免影响后续串行查询
+        self.pending_replace_regex.clear();
+        self.pending_replace_column.clear();
+        self.pending_replace_result.clear();
+        self.pending_sorted_result = false;
+        self.pending_compare = None;
+        self.pending_json_result = None;
+
+        match written? {
+            Some(message) => Err(anyhow!(message)),
+            None => Ok(()),
+        }
+    }
+
+    /// Write the outcome of each statement of a concurrent block, in block
+    /// order, formatted like a serial statement with its own modifiers.
+    /// Returns the first failure (unexpected error, expected error missing).
+    fn write_concurrent_results(
+        &mut self,
+        results: Vec<ConcurrentResult>,
+    ) -> Result<Option<String>> {
+        let mut failure = None;
+        for (index, result, hang) in results {
+            let query = self.concurrent_queries[index].clone();
+            self.inject_query_options(&query.options);
+            let expected_errors: Vec<String> = self.expected_errors.iter().cloned().collect();
+            let had_failure = failure.is_some();
+
+            let output = match (result, hang) {
+                // 超时被 KILL 的语句不受 --error 约束
+                (_, Some(hang)) => {
+                    failure.get_or_insert(hang.describe(&query.query, query.line));
+                    String::new()
+                }
+                (Ok((columns, rows, warnings)), None) => {
                     if !expected_errors.is_empty() {
                         // Expected error but query succeeded
                         let err_msg = format!(
                             "Expected error(s) {:?}, but query succeeded",
                             expected_errors
                         );
-                        output_parts.push(format!("UNEXPECTED_SUCCESS: {}", err_msg));
-                    } else {
-                        // Apply one-time regex replacements if any
-                        let mut final_output = output.clone();
-                        self.apply_regex_replacements(&mut final_output);
-                        output_parts.push(final_output);
+                        if self.args.check_err {
+                            failure.get_or_insert(err_msg);
+                        } else {
+                            warn!("{}", err_msg);
+                        }
                     }
+                    let mut output = self.format_query_result_to_string(&columns, &rows)?;
+                    let mut warnings = self.warnings.format(&warnings);
+                    self.apply_replacements(&mut warnings);
+                    output.push_str(&warnings);
+                    output
                 }
-                Err(e) => {
+                (Err(e), None) => {
+                    let error_str = self.error_handler.format_error(&e);
                     if !expected_errors.is_empty()
-                        && self.error_handler.check_expected_error(e, expected_errors)
+                        && self.error_handler.check_expected_error(&e, &expected_errors)
                     {
-                        let error_str = self.error_handler.format_error(e);
-                        output_parts.push(error_str);
+                        let mut output = format!("{}\n", error_str);
+                        self.apply_replacements(&mut output);
+                        output
                     } else if expected_errors.is_empty() {
                         // Unexpected error
-                        let error_str = self.error_handler.format_error(e);
-                        output_parts.push(format!("UNEXPECTED_ERROR: {}", error_str));
+                        failure.get_or_insert(format!("Unexpected error in concurrent block: {}", error_str));
+                        String::new()
                     } else {
                         // Expected different error
-                        let error_str = self.error_handler.format_error(e);
-                        output_parts.push(format!(
-                            "WRONG_ERROR: Expected {:?}, got {}",
+                        let err_msg = format!(
+                            "Expected error(s) {:?}, but got: {}",
                             expected_errors, error_str
-                        ));
+                        );
+                        if self.args.check_err {
+                            failure.get_or_insert(err_msg);
+                        } else {
+                            warn!("{}", err_msg);
+                        }
+                        String::new()
                     }
                 }
+            };
+            // 一次性修饰符只作用于该语句
+            self.take_pending_options();
+            // 失败详情指向块内首个失败的语句
+            if !had_failure && failure.is_some() {
+                self.set_current_query(query.query.clone(), query.line);
             }
-        }
 
-        // 并发查询结果不写入到 result 文件
-        // 查询已经执行完毕，但是输出被忽略
-        // 这确保并发块中的查询执行但不影响 result 文件内容
-
-        self.in_concurrent_block = false;
-        self.concurrent_queries.clear();
-        // 并发块结束后，清理一次性修饰符，避免影响后续串行查询
-        self.pending_replace_regex.clear();
-        self.pending_sorted_result = false;
-
-        Ok(())
+            // 语句不产生输出时（如 INSERT）不写空行
+            if !output.is_empty() && self.enable_result_log {
+                self.compare_with_result(&output)?;
+            }
+        }
+        Ok(failure)
     }
 }
 
+/// Outcome of one statement of a concurrent block: columns, formatted rows and
+/// the SHOW WARNINGS rows of its worker connection
+type ConcurrentOutput = (Vec<ColumnMeta>, Vec<Vec<String>>, Vec<Vec<String>>);
+
+/// A statement's index in the block, its outcome and the watchdog's diagnostics
+/// if it timed out
+type ConcurrentResult = (usize, Result<ConcurrentOutput, mysql::Error>, Option<HangDiagnostics>);
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::cli::Args;
+    use crate::util::charset::parse_charset;
     use log::warn;
     use std::fs::{self, File};
     use std::io::Write;
@@ -1881,6 +3214,21 @@ mod tests {
             allure_dir: "".to_string(),
             parallel: 1,
             max_connections: 0,
+            legacy_result_format: false,
+            max_loop_iterations: 10_000,
+            vardir: "var".to_string(),
+            mysql_client: "mysql".to_string(),
+            ps_protocol: false,
+            view_protocol: false,
+            sp_protocol: false,
+            query_timeout: None,
+            test_timeout: None,
+            max_diff_lines: 200,
+            continue_on_mismatch: false,
+            review: false,
+            record_pending: false,
+            character_set: None,
+            file_charset: None,
         };
 
         // Note: This test would require a running MySQL server to actually work
@@ -1938,6 +3286,21 @@ mod tests {
             allure_dir: "".to_string(),
             parallel: 1,
             max_connections: 0,
+            legacy_result_format: false,
+            max_loop_iterations: 10_000,
+            vardir: "var".to_string(),
+            mysql_client: "mysql".to_string(),
+            ps_protocol: false,
+            view_protocol: false,
+            sp_protocol: false,
+            query_timeout: None,
+            test_timeout: None,
+            max_diff_lines: 200,
+            continue_on_mismatch: false,
+            review: false,
+            record_pending: false,
+            character_set: None,
+            file_charset: None,
         };
 
         let mut tester = match Tester::new(args) {
@@ -1952,7 +3315,7 @@ mod tests {
 
         // 检查输出结果已经排序
         let output = String::from_utf8(tester.output_buffer.clone()).unwrap();
-        assert_eq!(output, "1\n2\n");
+        assert_eq!(output, "val\n1\n2\n");
 
         // 清理
         fs::remove_file(test_file_path).unwrap();
@@ -2007,6 +3370,21 @@ mod tests {
             allure_dir: "".to_string(),
             parallel: 1,
             max_connections: 0,
+            legacy_result_format: false,
+            max_loop_iterations: 10_000,
+            vardir: "var".to_string(),
+            mysql_client: "mysql".to_string(),
+            ps_protocol: false,
+            view_protocol: false,
+            sp_protocol: false,
+            query_timeout: None,
+            test_timeout: None,
+            max_diff_lines: 200,
+            continue_on_mismatch: false,
+            review: false,
+            record_pending: false,
+            character_set: None,
+            file_charset: None,
         };
 
         let mut tester = match Tester::new(args) {
@@ -2020,7 +3398,7 @@ mod tests {
         assert!(result.success);
 
         let output = String::from_utf8(tester.output_buffer.clone()).unwrap();
-        assert_eq!(output, "abcXXX\n");
+        assert_eq!(output, "val\nabcXXX\n");
 
         fs::remove_file(test_file_path).unwrap();
         let result_file_path = std::path::Path::new("r").join(format!("{}.result", test_name));
@@ -2062,6 +3440,21 @@ mod tests {
             allure_dir: "".to_string(),
             parallel: 1,
             max_connections: 0,
+            legacy_result_format: false,
+            max_loop_iterations: 10_000,
+            vardir: "var".to_string(),
+            mysql_client: "mysql".to_string(),
+            ps_protocol: false,
+            view_protocol: false,
+            sp_protocol: false,
+            query_timeout: None,
+            test_timeout: None,
+            max_diff_lines: 200,
+            continue_on_mismatch: false,
+            review: false,
+            record_pending: false,
+            character_set: None,
+            file_charset: None,
         };
 
         // This test doesn't actually create a tester since it would require MySQL
@@ -2124,6 +3517,21 @@ mod tests {
             allure_dir: "".to_string(),
             parallel: 1,
             max_connections: 0,
+            legacy_result_format: false,
+            max_loop_iterations: 10_000,
+            vardir: "var".to_string(),
+            mysql_client: "mysql".to_string(),
+            ps_protocol: false,
+            view_protocol: false,
+            sp_protocol: false,
+            query_timeout: None,
+            test_timeout: None,
+            max_diff_lines: 200,
+            continue_on_mismatch: false,
+            review: false,
+            record_pending: false,
+            character_set: None,
+            file_charset: None,
         };
 
         let mut tester = match Tester::new(args) {
@@ -2141,6 +3549,240 @@ mod tests {
         fs::remove_file(test_file_path).unwrap();
         fs::remove_file(result_file_path).unwrap();
     }
+
+    #[test]
+    fn test_find_resync_point() {
+        let expected = ["SELECT 1", "1", "1", "SELECT 2", "2", "SELECT 2", "3"];
+        let exact = CompareOptions::default();
+        // The first position where the whole chunk matches
+        assert_eq!(find_resync_point(&expected, 0, &["SELECT 2"], &exact), Some(3));
+        assert_eq!(find_resync_point(&expected, 0, &["SELECT 2", "3"], &exact), Some(5));
+        assert_eq!(find_resync_point(&expected, 4, &["SELECT 2"], &exact), Some(5));
+        assert_eq!(find_resync_point(&expected, 0, &["SELECT 3"], &exact), None);
+        assert_eq!(find_resync_point(&expected, 0, &[], &exact), None);
+        // After an inserted result line the match is further down, after a
+        // removed one at the current position
+        let result = ["a", "X", "c", "inserted", "d", "e", "g"];
+        assert_eq!(find_resync_point(&result, 3, &["e"], &exact), Some(5));
+        assert_eq!(find_resync_point(&result, 6, &["g"], &exact), Some(6));
+        // Result file markers match as in the comparison
+        let marked = ["SELECT NOW()", "{{TIMESTAMP}}"];
+        assert_eq!(find_resync_point(&marked, 0, &["SELECT NOW()", "2024-01-02 03:04:05"], &exact), Some(0));
+        let tolerant = CompareOptions::parse("float_epsilon=1e-6").unwrap();
+        assert_eq!(find_resync_point(&expected, 0, &["SELECT 2", "3.0000001"], &tolerant), Some(5));
+    }
+
+    #[test]
+    #[ignore = "Requires database connection and file system access - run with integration tests"]
+    fn test_continue_on_mismatch() {
+        let test_name = "continue_on_mismatch_test";
+        let test_dir = std::path::Path::new("t");
+        let result_dir = std::path::Path::new("r");
+        let args = Args {
+            host: "127.0.0.1".to_string(),
+            port: "3306".to_string(),
+            user: "root".to_string(),
+            passwd: "123456".to_string(),
+            log_level: "error".to_string(),
+            record: false,
+            params: "".to_string(),
+            all: false,
+            reserve_schema: false,
+            xunit_file: "".to_string(),
+            retry_conn_count: 1,
+            check_err: false,
+            collation_disable: false,
+            extension: "result".to_string(),
+            result_dir: "r".to_string(),
+            email_enable: false,
+            email_smtp_host: "".to_string(),
+            email_smtp_port: 587,
+            email_username: "".to_string(),
+            email_password: "".to_string(),
+            email_from: "".to_string(),
+            email_to: "".to_string(),
+            email_enable_tls: false,
+            fail_fast: false,
+            test_files: vec![],
+            report_format: "terminal".to_string(),
+            allure_dir: "".to_string(),
+            parallel: 1,
+            max_connections: 0,
+            legacy_result_format: false,
+            max_loop_iterations: 10_000,
+            vardir: "var".to_string(),
+            mysql_client: "mysql".to_string(),
+            ps_protocol: false,
+            view_protocol: false,
+            sp_protocol: false,
+            query_timeout: None,
+            test_timeout: None,
+            max_diff_lines: 200,
+            continue_on_mismatch: true,
+            review: false,
+            record_pending: false,
+            character_set: None,
+            file_charset: None,
+        };
+
+        let mut tester = match Tester::new(args) {
+            Ok(t) => t,
+            Err(e) => {
+                warn!("Skipping test_continue_on_mismatch due to DB connection error: {}. This test requires a running MySQL server.", e);
+                return;
+            }
+        };
+
+        fs::create_dir_all(test_dir).unwrap();
+        fs::create_dir_all(result_dir).unwrap();
+
+        let test_file_path = test_dir.join(format!("{}.test", test_name));
+        let mut file = File::create(&test_file_path).unwrap();
+        for line in ["a", "b", "c", "d", "e", "f", "g"] {
+            writeln!(file, "--echo {}", line).unwrap();
+        }
+
+        // A changed line (b), an inserted line before d and a removed line (f)
+        let result_file_path = result_dir.join(format!("{}.result", test_name));
+        fs::write(&result_file_path, "a\nX\nc\ninserted\nd\ne\ng\n").unwrap();
+        let reject_file_path = result_dir.join(format!("{}.reject", test_name));
+
+        let result = tester.run_test_file(test_name).unwrap();
+        assert!(!result.success);
+
+        // Every difference is collected; after each one the comparison
+        // resynchronises at the next matching command
+        let differences: Vec<(&str, &str)> = result
+            .query_failures
+            .iter()
+            .map(|failure| (failure.expected.as_str(), failure.actual.as_str()))
+            .collect();
+        assert_eq!(differences, vec![("X", "b"), ("inserted", "d"), ("g", "f")]);
+        assert_eq!(result.failed_queries, 3);
+        assert!(result.errors[0].contains("result line 2"));
+        assert!(result.errors[1].contains("result line 4"));
+        assert!(result.errors[2].contains("result line 7"));
+
+        // The reject file holds the whole actual output, the report diff lists each difference
+        let reject = fs::read_to_string(&reject_file_path).unwrap();
+        assert_eq!(reject, "a\nb\nc\nd\ne\nf\ng\n");
+        let diff = result.diff.unwrap();
+        for line in ["-X", "+b", "-inserted", "+f"] {
+            assert!(diff.lines().any(|l| l == line), "missing '{}' in diff:\n{}", line, diff);
+        }
+
+        fs::remove_file(test_file_path).unwrap();
+        fs::remove_file(result_file_path).unwrap();
+        fs::remove_file(reject_file_path).unwrap();
+    }
+
+    #[test]
+    #[ignore = "Requires database connection and file system access - run with integration tests"]
+    fn test_loop_limit_leaves_loop() {
+        let test_name = "loop_limit_test";
+        let test_dir = std::path::Path::new("t");
+        let result_dir = std::path::Path::new("r");
+        let args = Args {
+            host: "127.0.0.1".to_string(),
+            port: "3306".to_string(),
+            user: "root".to_string(),
+            passwd: "123456".to_string(),
+            log_level: "error".to_string(),
+            record: true,
+            params: "".to_string(),
+            all: false,
+            reserve_schema: false,
+            xunit_file: "".to_string(),
+            retry_conn_count: 1,
+            check_err: false,
+            collation_disable: false,
+            extension: "result".to_string(),
+            result_dir: "r".to_string(),
+            email_enable: false,
+            email_smtp_host: "".to_string(),
+            email_smtp_port: 587,
+            email_username: "".to_string(),
+            email_password: "".to_string(),
+            email_from: "".to_string(),
+            email_to: "".to_string(),
+            email_enable_tls: false,
+            fail_fast: false,
+            test_files: vec![],
+            report_format: "terminal".to_string(),
+            allure_dir: "".to_string(),
+            parallel: 1,
+            max_connections: 0,
+            legacy_result_format: false,
+            max_loop_iterations: 3,
+            vardir: "var".to_string(),
+            mysql_client: "mysql".to_string(),
+            ps_protocol: false,
+            view_protocol: false,
+            sp_protocol: false,
+            query_timeout: None,
+            test_timeout: None,
+            max_diff_lines: 200,
+            continue_on_mismatch: true,
+            review: false,
+            record_pending: false,
+            character_set: None,
+            file_charset: None,
+        };
+
+        let mut tester = match Tester::new(args) {
+            Ok(t) => t,
+            Err(e) => {
+                warn!("Skipping test_loop_limit_leaves_loop due to DB connection error: {}. This test requires a running MySQL server.", e);
+                return;
+            }
+        };
+
+        fs::create_dir_all(test_dir).unwrap();
+        fs::create_dir_all(result_dir).unwrap();
+
+        // The inner loop hits the limit in each outer iteration
+        let test_file_path = test_dir.join(format!("{}.test", test_name));
+        fs::write(
+            &test_file_path,
+            "--let $outer = 0\n\
+             while ($outer < 2) {\n\
+               inc $outer\n\
+               while (1) {\n\
+                 --echo inner\n\
+               }\n\
+               --echo outer $outer\n\
+             }\n\
+             --echo done\n",
+        )
+        .unwrap();
+        let result_file_path = result_dir.join(format!("{}.result", test_name));
+
+        let result = tester.run_test_file(test_name).unwrap();
+        assert_eq!(result.failed_queries, 2);
+        assert!(result.errors[0].contains("exceeded the maximum of 3 iterations"));
+        assert!(tester.while_stack.is_empty());
+
+        // After each failure the test continues behind the inner loop and the
+        // outer loop still runs its second iteration
+        let recorded = fs::read_to_string(&result_file_path).unwrap();
+        assert_eq!(
+            recorded,
+            "inner\ninner\ninner\nouter 1\ninner\ninner\ninner\nouter 2\ndone\n"
+        );
+
+        fs::remove_file(test_file_path).unwrap();
+        fs::remove_file(result_file_path).unwrap();
+    }
+
+    #[test]
+    fn test_connection_params() {
+        let mut args = Args::default();
+        assert_eq!(connection_params(&args), "");
+        args.character_set = parse_charset("gbk").ok();
+        assert_eq!(connection_params(&args), "charset=gbk");
+        args.params = "ssl=false".to_string();
+        assert_eq!(connection_params(&args), "ssl=false&charset=gbk");
+    }
 }
 
 // === New enhanced syntax methods ===
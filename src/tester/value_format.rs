@@ -0,0 +1,285 @@
+//! Rendering of result values (--value_format)
+//!
+//! Every result cell goes through `ValueFormat::format`, for the serial,
+//! prepared statement and concurrent paths alike. Text protocol values arrive
+//! as bytes; typed values come from prepared statements and are rendered the
+//! way the text protocol would send them, so results are identical with and
+//! without --ps-protocol.
+//!
+//! By default binary strings are written as they are (like mysqltest) and SQL
+//! NULL as `NULL`. Per test, `--value_format binary=hex null=<NULL>` renders
+//! BINARY/VARBINARY/BLOB and BIT values as `0x...` (like the mysql client's
+//! `--binary-as-hex`) or escaped, and SQL NULL with its own text so it can be
+//! told apart from the string `'NULL'`.
+
+use crate::util::charset::Charset;
+use anyhow::{anyhow, Result};
+use mysql::consts::ColumnType;
+use mysql::{Column, Value};
+
+/// Collation id of the `binary` character set
+const BINARY_CHARSET: u16 = 63;
+
+/// How binary strings and BIT values are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BinaryFormat {
+    /// Bytes as they are (invalid UTF-8 replaced)
+    #[default]
+    Raw,
+    /// `0x` followed by upper case hex digits
+    Hex,
+    /// Printable ASCII as is, other bytes as `\0`, `\n`, `\t`, `\r`, `\\` or `\xNN`
+    Escape,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueFormat {
+    pub binary: BinaryFormat,
+    /// Text written for SQL NULL
+    pub null: String,
+}
+
+impl Default for ValueFormat {
+    fn default() -> Self {
+        ValueFormat {
+            binary: BinaryFormat::Raw,
+            null: "NULL".to_string(),
+        }
+    }
+}
+
+impl ValueFormat {
+    /// Parse `binary=raw|hex|escape null=<text>`; an empty spec is the default format
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut format = ValueFormat::default();
+        for option in spec.split_whitespace() {
+            match option.split_once('=') {
+                Some(("binary", value)) => {
+                    format.binary = match value.to_ascii_lowercase().as_str() {
+                        "raw" => BinaryFormat::Raw,
+                        "hex" => BinaryFormat::Hex,
+                        "escape" => BinaryFormat::Escape,
+                        other => {
+                            return Err(anyhow!(
+                                "Invalid binary format '{}' (expected raw, hex or escape)",
+                                other
+                            ))
+                        }
+                    }
+                }
+                Some(("null", value)) => format.null = value.to_string(),
+                _ => {
+                    return Err(anyhow!(
+                        "Unknown value format option '{}' (expected binary=<raw|hex|escape> or null=<text>)",
+                        option
+                    ))
+                }
+            }
+        }
+        Ok(format)
+    }
+
+    /// Render one result value of `column`; text arrives in `charset`
+    /// (the connection's `character_set_results`)
+    pub fn format(&self, value: &Value, column: &Column, charset: Charset) -> String {
+        // Fractional digits of temporal columns; 31 means "not fixed" for floats
+        let decimals = column.decimals() as usize;
+        let fraction = |us: u32| {
+            if (1..=6).contains(&decimals) {
+                format!(".{}", &format!("{:06}", us)[..decimals])
+            } else {
+                String::new()
+            }
+        };
+
+        match value {
+            Value::NULL => self.null.clone(),
+            Value::Bytes(b) if is_binary(column) => self.format_binary(b),
+            Value::Bytes(b) => charset.decode(b).into_owned(),
+            Value::Int(n) => n.to_string(),
+            Value::UInt(n) => n.to_string(),
+            Value::Float(f) if decimals < 31 => format!("{:.*}", decimals, f),
+            Value::Float(f) => format_float(*f),
+            Value::Double(d) if decimals < 31 => format!("{:.*}", decimals, d),
+            Value::Double(d) if d.abs() >= 1e15 => format!("{:e}", d),
+            Value::Double(d) => d.to_string(),
+            Value::Date(y, m, d, ..) if column.column_type() == ColumnType::MYSQL_TYPE_DATE => {
+                format!("{:04}-{:02}-{:02}", y, m, d)
+            }
+            Value::Date(y, m, d, hh, mm, ss, us) => format!(
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}{}",
+                y,
+                m,
+                d,
+                hh,
+                mm,
+                ss,
+                fraction(*us)
+            ),
+            Value::Time(neg, d, hh, mm, ss, us) => {
+                let total_hours = *d * 24 + *hh as u32;
+                format!(
+                    "{}{:02}:{:02}:{:02}{}",
+                    if *neg { "-" } else { "" },
+                    total_hours,
+                    mm,
+                    ss,
+                    fraction(*us)
+                )
+            }
+        }
+    }
+
+    fn format_binary(&self, bytes: &[u8]) -> String {
+        match self.binary {
+            BinaryFormat::Raw => String::from_utf8_lossy(bytes).into_owned(),
+            BinaryFormat::Hex => {
+                let mut hex = String::with_capacity(2 + bytes.len() * 2);
+                hex.push_str("0x");
+                for byte in bytes {
+                    hex.push_str(&format!("{:02X}", byte));
+                }
+                hex
+            }
+            BinaryFormat::Escape => {
+                let mut escaped = String::with_capacity(bytes.len());
+                for &byte in bytes {
+                    match byte {
+                        0 => escaped.push_str("\\0"),
+                        b'\n' => escaped.push_str("\\n"),
+                        b'\t' => escaped.push_str("\\t"),
+                        b'\r' => escaped.push_str("\\r"),
+                        b'\\' => escaped.push_str("\\\\"),
+                        0x20..=0x7e => escaped.push(byte as char),
+                        _ => escaped.push_str(&format!("\\x{:02X}", byte)),
+                    }
+                }
+                escaped
+            }
+        }
+    }
+}
+
+/// BIT values and strings of the binary character set (BINARY, VARBINARY, BLOB).
+/// Numeric columns also report charset 63, so the type decides.
+fn is_binary(column: &Column) -> bool {
+    match column.column_type() {
+        ColumnType::MYSQL_TYPE_BIT => true,
+        ColumnType::MYSQL_TYPE_STRING
+        | ColumnType::MYSQL_TYPE_VAR_STRING
+        | ColumnType::MYSQL_TYPE_VARCHAR
+        | ColumnType::MYSQL_TYPE_TINY_BLOB
+        | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+        | ColumnType::MYSQL_TYPE_LONG_BLOB
+        | ColumnType::MYSQL_TYPE_BLOB => column.character_set() == BINARY_CHARSET,
+        _ => false,
+    }
+}
+
+/// FLOAT without fixed decimals, as the server writes it: 6 significant
+/// digits, scientific notation for very large or small values
+fn format_float(f: f32) -> String {
+    if f == 0.0 || !f.is_finite() {
+        return f.to_string();
+    }
+    let scientific = format!("{:.5e}", f);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let trim = |s: &str| {
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            s.to_string()
+        }
+    };
+    if !(-5..6).contains(&exponent) {
+        format!("{}e{}", trim(mantissa), exponent)
+    } else {
+        trim(&format!("{:.*}", (5 - exponent).max(0) as usize, f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binary_column(column_type: ColumnType) -> Column {
+        Column::new(column_type).with_character_set(BINARY_CHARSET)
+    }
+
+    #[test]
+    fn test_binary_values_render_like_text() {
+        let format = ValueFormat::default();
+        let date = Column::new(ColumnType::MYSQL_TYPE_DATE);
+        let datetime6 = Column::new(ColumnType::MYSQL_TYPE_DATETIME).with_decimals(6);
+        let datetime = Column::new(ColumnType::MYSQL_TYPE_DATETIME);
+        let time3 = Column::new(ColumnType::MYSQL_TYPE_TIME).with_decimals(3);
+        let double = Column::new(ColumnType::MYSQL_TYPE_DOUBLE).with_decimals(31);
+        let double2 = Column::new(ColumnType::MYSQL_TYPE_DOUBLE).with_decimals(2);
+        let float = Column::new(ColumnType::MYSQL_TYPE_FLOAT).with_decimals(31);
+
+        let ts = Value::Date(2024, 1, 2, 3, 4, 5, 120000);
+        assert_eq!(format.format(&ts, &date, Charset::UTF8MB4), "2024-01-02");
+        assert_eq!(format.format(&ts, &datetime, Charset::UTF8MB4), "2024-01-02 03:04:05");
+        assert_eq!(format.format(&ts, &datetime6, Charset::UTF8MB4), "2024-01-02 03:04:05.120000");
+        assert_eq!(
+            format.format(&Value::Time(true, 1, 2, 3, 4, 500000), &time3, Charset::UTF8MB4),
+            "-26:03:04.500"
+        );
+        assert_eq!(format.format(&Value::Double(1.5), &double, Charset::UTF8MB4), "1.5");
+        assert_eq!(format.format(&Value::Double(1e20), &double, Charset::UTF8MB4), "1e20");
+        assert_eq!(format.format(&Value::Double(1.5), &double2, Charset::UTF8MB4), "1.50");
+        assert_eq!(format.format(&Value::Float(0.1), &float, Charset::UTF8MB4), "0.1");
+        assert_eq!(format.format(&Value::Float(1.0 / 3.0), &float, Charset::UTF8MB4), "0.333333");
+        assert_eq!(format.format(&Value::Float(-1234567.0), &float, Charset::UTF8MB4), "-1.23457e6");
+        assert_eq!(format.format(&Value::Float(0.00001), &float, Charset::UTF8MB4), "0.00001");
+        assert_eq!(format.format(&Value::NULL, &float, Charset::UTF8MB4), "NULL");
+    }
+
+    #[test]
+    fn test_binary_formats() {
+        let varbinary = binary_column(ColumnType::MYSQL_TYPE_VAR_STRING);
+        let bit = Column::new(ColumnType::MYSQL_TYPE_BIT).with_character_set(BINARY_CHARSET);
+        let varchar = Column::new(ColumnType::MYSQL_TYPE_VAR_STRING).with_character_set(255);
+        let number = binary_column(ColumnType::MYSQL_TYPE_LONGLONG);
+        let bytes = Value::Bytes(vec![b'a', 0, b'\n', 0xff]);
+
+        let hex = ValueFormat::parse("binary=hex").unwrap();
+        assert_eq!(hex.format(&bytes, &varbinary, Charset::UTF8MB4), "0x61000AFF");
+        assert_eq!(hex.format(&Value::Bytes(vec![5]), &bit, Charset::UTF8MB4), "0x05");
+        assert_eq!(hex.format(&Value::Bytes(Vec::new()), &varbinary, Charset::UTF8MB4), "0x");
+        // Text and numbers are not binary strings
+        assert_eq!(hex.format(&Value::Bytes(b"abc".to_vec()), &varchar, Charset::UTF8MB4), "abc");
+        assert_eq!(hex.format(&Value::Bytes(b"42".to_vec()), &number, Charset::UTF8MB4), "42");
+
+        let escape = ValueFormat::parse("binary=escape").unwrap();
+        assert_eq!(escape.format(&bytes, &varbinary, Charset::UTF8MB4), "a\\0\\n\\xFF");
+
+        let raw = ValueFormat::default();
+        assert_eq!(raw.format(&Value::Bytes(b"abc".to_vec()), &varbinary, Charset::UTF8MB4), "abc");
+    }
+
+    #[test]
+    fn test_text_in_connection_charset() {
+        let format = ValueFormat::default();
+        let varchar = Column::new(ColumnType::MYSQL_TYPE_VAR_STRING).with_character_set(28);
+        let gbk = crate::util::charset::parse_charset("gbk").unwrap();
+        let bytes = Value::Bytes(vec![0xD6, 0xD0, 0xCE, 0xC4]);
+        assert_eq!(format.format(&bytes, &varchar, gbk), "中文");
+    }
+
+    #[test]
+    fn test_null_text() {
+        let format = ValueFormat::parse("null=<NULL>").unwrap();
+        let varchar = Column::new(ColumnType::MYSQL_TYPE_VAR_STRING);
+        assert_eq!(format.format(&Value::NULL, &varchar, Charset::UTF8MB4), "<NULL>");
+        assert_eq!(format.format(&Value::Bytes(b"NULL".to_vec()), &varchar, Charset::UTF8MB4), "NULL");
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ValueFormat::parse("").unwrap(), ValueFormat::default());
+        assert!(ValueFormat::parse("binary=base64").is_err());
+        assert!(ValueFormat::parse("hex").is_err());
+    }
+}
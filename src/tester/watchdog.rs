@@ -0,0 +1,266 @@
+//! Statement timeouts (--query-timeout, --test-timeout, --timeout)
+//!
+//! While a statement runs, a watchdog thread waits for it to finish. When the
+//! timeout expires first, the watchdog records `SHOW FULL PROCESSLIST` and the
+//! InnoDB transaction/lock section from a side connection, then issues
+//! `KILL QUERY` so the blocked statement returns and the test can fail.
+
+use anyhow::{anyhow, Result};
+use log::{debug, warn};
+use mysql::prelude::Queryable;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// What the watchdog saw when the timeout expired
+#[derive(Debug, Clone)]
+pub struct HangDiagnostics {
+    pub timeout: Duration,
+    /// `SHOW FULL PROCESSLIST`, one tab separated row per line
+    pub processlist: String,
+    /// TRANSACTIONS section of `SHOW ENGINE INNODB STATUS`
+    pub innodb_locks: String,
+}
+
+impl HangDiagnostics {
+    /// Failure detail for the statement at `line`
+    pub fn describe(&self, sql: &str, line: usize) -> String {
+        format!(
+            "Statement timed out after {} at line {}: {}\n\nSHOW PROCESSLIST:\n{}\n\nInnoDB lock state:\n{}",
+            format_duration(self.timeout),
+            line,
+            sql,
+            self.processlist.trim_end(),
+            self.innodb_locks.trim_end()
+        )
+    }
+}
+
+/// Watches one statement running on connection `connection_id`
+#[derive(Debug)]
+pub struct Watchdog {
+    done: Sender<()>,
+    handle: JoinHandle<Option<HangDiagnostics>>,
+}
+
+impl Watchdog {
+    /// Start watching. `pool` provides the side connection used to kill the statement.
+    pub fn arm(pool: mysql::Pool, connection_id: u32, timeout: Duration) -> Self {
+        Self::arm_with(timeout, move || kill_and_diagnose(&pool, connection_id, timeout))
+    }
+
+    /// Start watching, running `on_timeout` if the timeout expires before `disarm`
+    fn arm_with<F>(timeout: Duration, on_timeout: F) -> Self
+    where
+        F: FnOnce() -> HangDiagnostics + Send + 'static,
+    {
+        let (done, finished) = mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || match finished.recv_timeout(timeout) {
+            Err(RecvTimeoutError::Timeout) => Some(on_timeout()),
+            _ => None,
+        });
+        Watchdog { done, handle }
+    }
+
+    /// Stop watching after the statement returned. Returns the diagnostics if
+    /// the timeout expired first (the statement was killed).
+    pub fn disarm(self) -> Option<HangDiagnostics> {
+        let _ = self.done.send(());
+        self.handle.join().unwrap_or(None)
+    }
+}
+
+fn kill_and_diagnose(pool: &mysql::Pool, connection_id: u32, timeout: Duration) -> HangDiagnostics {
+    warn!(
+        "Statement on connection {} exceeded {}, killing it",
+        connection_id,
+        format_duration(timeout)
+    );
+    let mut diagnostics = HangDiagnostics {
+        timeout,
+        processlist: String::new(),
+        innodb_locks: String::new(),
+    };
+
+    let mut conn = match pool.get_conn() {
+        Ok(conn) => conn,
+        Err(e) => {
+            diagnostics.processlist = format!("(no side connection: {})", e);
+            return diagnostics;
+        }
+    };
+
+    diagnostics.processlist = match conn.query::<mysql::Row, _>("SHOW FULL PROCESSLIST") {
+        Ok(rows) => rows.into_iter().map(format_row).collect::<Vec<_>>().join("\n"),
+        Err(e) => format!("(unavailable: {})", e),
+    };
+    diagnostics.innodb_locks = match conn.query::<mysql::Row, _>("SHOW ENGINE INNODB STATUS") {
+        Ok(rows) => rows
+            .into_iter()
+            .filter_map(|row| row.get_opt::<String, _>(2).and_then(|v| v.ok()))
+            .map(|status| innodb_transactions(&status).to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Err(e) => format!("(unavailable: {})", e),
+    };
+
+    if let Err(e) = conn.query_drop(format!("KILL QUERY {}", connection_id)) {
+        warn!("KILL QUERY {} failed: {}", connection_id, e);
+    } else {
+        debug!("Killed query on connection {}", connection_id);
+    }
+    diagnostics
+}
+
+fn format_row(row: mysql::Row) -> String {
+    row.unwrap()
+        .into_iter()
+        .map(|value| match value {
+            mysql::Value::NULL => "NULL".to_string(),
+            mysql::Value::Bytes(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+            other => other.as_sql(true),
+        })
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
+/// The TRANSACTIONS section of `SHOW ENGINE INNODB STATUS` (lock waits and
+/// held locks); the whole status when the section is not found
+fn innodb_transactions(status: &str) -> &str {
+    let is_rule = |line: &str| !line.is_empty() && line.chars().all(|c| c == '-');
+    let Some(start) = status.find("\nTRANSACTIONS\n") else {
+        return status;
+    };
+    // Skip the heading and its underline
+    let body_start = status[start + 1..]
+        .match_indices('\n')
+        .nth(1)
+        .map_or(status.len(), |(offset, _)| start + 1 + offset + 1);
+
+    // The next section starts with a rule, its heading and another rule
+    let body = &status[body_start..];
+    let lines: Vec<&str> = body.split('\n').collect();
+    let mut offset = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if is_rule(line) && lines.get(i + 2).is_some_and(|l| is_rule(l)) {
+            return body[..offset].trim_end();
+        }
+        offset += line.len() + 1;
+    }
+    body.trim_end()
+}
+
+/// Parse a timeout such as `30`, `30s`, `500ms`, `2m` or `1h` (plain numbers
+/// are seconds). `0` disables the timeout.
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow!("Invalid timeout: '{}'", value))?;
+    let seconds = match unit.trim() {
+        "" | "s" => number,
+        "ms" => number / 1000.0,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(anyhow!("Invalid timeout unit in '{}' (use ms, s, m or h)", value)),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|e| anyhow!("Invalid timeout '{}': {}", value, e))
+}
+
+/// Render a duration the way it is usually written in a test (`30s`, `1.5s`, `500ms`)
+pub fn format_duration(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    if millis < 1000 && millis != 0 {
+        format!("{}ms", millis)
+    } else {
+        format!("{}s", millis as f64 / 1000.0)
+    }
+}
+
+/// Effective timeout of the next statement: the statement timeout, capped by
+/// the time left before the test deadline
+pub fn statement_timeout(query_timeout: Option<Duration>, test_deadline: Option<Instant>) -> Option<Duration> {
+    let remaining = test_deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+    match (query_timeout, remaining) {
+        (Some(query), Some(remaining)) => Some(query.min(remaining)),
+        (query, remaining) => query.or(remaining),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration(" 2m ").unwrap(), Duration::from_secs(120));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("0").unwrap(), Duration::ZERO);
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("10d").is_err());
+        assert!(parse_duration("99999999999999999999999h").is_err());
+    }
+
+    fn diagnostics(timeout: Duration) -> HangDiagnostics {
+        HangDiagnostics {
+            timeout,
+            processlist: "1\troot\tQuery\tSELECT SLEEP(5)".to_string(),
+            innodb_locks: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_watchdog_kills_after_timeout() {
+        let timeout = Duration::from_millis(20);
+        let watchdog = Watchdog::arm_with(timeout, move || diagnostics(timeout));
+        std::thread::sleep(Duration::from_millis(200));
+        let hang = watchdog.disarm().expect("timeout should have expired");
+        assert_eq!(hang.timeout, timeout);
+        let detail = hang.describe("SELECT SLEEP(5)", 3);
+        assert!(detail.starts_with("Statement timed out after 20ms at line 3: SELECT SLEEP(5)"));
+        assert!(detail.contains("SHOW PROCESSLIST:\n1\troot\tQuery\tSELECT SLEEP(5)"));
+    }
+
+    #[test]
+    fn test_watchdog_disarmed_in_time() {
+        let timeout = Duration::from_secs(60);
+        let watchdog = Watchdog::arm_with(timeout, || panic!("statement must not be killed"));
+        assert!(watchdog.disarm().is_none());
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(30)), "30s");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1.5s");
+        assert_eq!(format_duration(Duration::from_millis(200)), "200ms");
+    }
+
+    #[test]
+    fn test_statement_timeout() {
+        let query = Some(Duration::from_secs(30));
+        assert_eq!(statement_timeout(None, None), None);
+        assert_eq!(statement_timeout(query, None), query);
+
+        let later = Instant::now() + Duration::from_secs(3600);
+        assert_eq!(statement_timeout(query, Some(later)), query);
+        let soon = statement_timeout(query, Some(Instant::now() + Duration::from_secs(5))).unwrap();
+        assert!(soon <= Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_innodb_transactions() {
+        let status = "=====\n------------\nTRANSACTIONS\n------------\nTrx id counter 1234\n---TRANSACTION 1233, ACTIVE 3 sec\n------- TRX HAS BEEN WAITING 3 SEC FOR THIS LOCK TO BE GRANTED:\n--------\nFILE I/O\n--------\nI/O thread 0\n";
+        assert_eq!(
+            innodb_transactions(status),
+            "Trx id counter 1234\n---TRANSACTION 1233, ACTIVE 3 sec\n------- TRX HAS BEEN WAITING 3 SEC FOR THIS LOCK TO BE GRANTED:"
+        );
+        assert_eq!(innodb_transactions("no sections"), "no sections");
+    }
+}
@@ -0,0 +1,86 @@
+//! Argument splitting helpers for mysqltest commands
+//!
+//! Commands such as `--replace_column` and `--replace_result` take a list of
+//! whitespace separated values where each value may be quoted to keep spaces.
+
+use anyhow::{anyhow, Result};
+
+/// Split command arguments on whitespace, honouring single and double quotes.
+///
+/// Inside a quoted value a backslash escapes the next character, so `"a \" b"`
+/// yields `a " b`. An empty quoted value (`""`) is kept as an empty string.
+pub fn split_args(input: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut chars = input.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\'' | '"' => {
+                let quote = ch;
+                let mut closed = false;
+                while let Some(inner) = chars.next() {
+                    if inner == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            current.push(escaped);
+                        }
+                    } else if inner == quote {
+                        closed = true;
+                        break;
+                    } else {
+                        current.push(inner);
+                    }
+                }
+                if !closed {
+                    return Err(anyhow!("Unterminated quoted value in: {}", input));
+                }
+                in_token = true;
+            }
+            c if c.is_whitespace() => {
+                if in_token {
+                    args.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+
+    if in_token {
+        args.push(current);
+    }
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_plain_args() {
+        let args = split_args("1 # 3  <TS>").unwrap();
+        assert_eq!(args, vec!["1", "#", "3", "<TS>"]);
+    }
+
+    #[test]
+    fn test_split_quoted_args() {
+        let args = split_args(r#"1 "two words" 'x' """#).unwrap();
+        assert_eq!(args, vec!["1", "two words", "x", ""]);
+    }
+
+    #[test]
+    fn test_split_escaped_quote() {
+        let args = split_args(r#""a \" b" c"#).unwrap();
+        assert_eq!(args, vec!["a \" b", "c"]);
+    }
+
+    #[test]
+    fn test_split_unterminated_quote() {
+        assert!(split_args("1 'oops").is_err());
+    }
+}
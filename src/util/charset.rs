@@ -0,0 +1,186 @@
+//! MySQL character sets for results and test files.
+//!
+//! Result bytes arrive in the connection's `character_set_results` and are
+//! decoded with the matching encoding. `.test`/`.result` files may be written
+//! in another encoding, named by a first line `# charset: <name>` or by
+//! `--file-charset`; they are decoded on read and encoded again on write.
+
+use anyhow::{anyhow, Context, Result};
+use encoding_rs::Encoding;
+use std::borrow::Cow;
+use std::path::Path;
+
+/// A MySQL character set with the encoding its bytes are decoded with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Charset {
+    name: &'static str,
+    encoding: &'static Encoding,
+}
+
+/// MySQL names and their encodings (latin1 is cp1252 in MySQL)
+const CHARSETS: &[(&str, &Encoding)] = &[
+    ("utf8mb4", encoding_rs::UTF_8),
+    ("utf8mb3", encoding_rs::UTF_8),
+    ("utf8", encoding_rs::UTF_8),
+    ("latin1", encoding_rs::WINDOWS_1252),
+    ("ascii", encoding_rs::WINDOWS_1252),
+    ("latin2", encoding_rs::ISO_8859_2),
+    ("greek", encoding_rs::ISO_8859_7),
+    ("hebrew", encoding_rs::ISO_8859_8),
+    ("cp1250", encoding_rs::WINDOWS_1250),
+    ("cp1251", encoding_rs::WINDOWS_1251),
+    ("cp1256", encoding_rs::WINDOWS_1256),
+    ("cp1257", encoding_rs::WINDOWS_1257),
+    ("koi8r", encoding_rs::KOI8_R),
+    ("koi8u", encoding_rs::KOI8_U),
+    ("gbk", encoding_rs::GBK),
+    ("gb2312", encoding_rs::GBK),
+    ("gb18030", encoding_rs::GB18030),
+    ("big5", encoding_rs::BIG5),
+    ("sjis", encoding_rs::SHIFT_JIS),
+    ("cp932", encoding_rs::SHIFT_JIS),
+    ("ujis", encoding_rs::EUC_JP),
+    ("eucjpms", encoding_rs::EUC_JP),
+    ("euckr", encoding_rs::EUC_KR),
+];
+
+impl Default for Charset {
+    fn default() -> Self {
+        Charset::UTF8MB4
+    }
+}
+
+impl Charset {
+    pub const UTF8MB4: Charset = Charset {
+        name: "utf8mb4",
+        encoding: encoding_rs::UTF_8,
+    };
+
+    /// MySQL character set name
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn is_utf8(&self) -> bool {
+        self.encoding == encoding_rs::UTF_8
+    }
+
+    /// Decode bytes of this character set; invalid sequences are replaced
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Cow<'a, str> {
+        if self.is_utf8() {
+            return String::from_utf8_lossy(bytes);
+        }
+        self.encoding.decode_without_bom_handling(bytes).0
+    }
+
+    /// Encode text in this character set (unmappable characters become `&#N;`)
+    pub fn encode<'a>(&self, text: &'a str) -> Cow<'a, [u8]> {
+        self.encoding.encode(text).0
+    }
+
+    /// Session settings for this connection charset. Statements are still sent
+    /// as UTF-8 (the driver only sends UTF-8 text), while literals take the
+    /// connection charset and results come back in it.
+    pub fn session_statement(&self) -> String {
+        format!(
+            "SET character_set_client = utf8mb4, character_set_connection = {0}, character_set_results = {0}",
+            self.name
+        )
+    }
+}
+
+/// Look up a MySQL character set name such as `gbk` or `latin1`
+pub fn parse_charset(name: &str) -> Result<Charset> {
+    let lower = name.trim().trim_matches(|c| c == '\'' || c == '"').to_ascii_lowercase();
+    CHARSETS
+        .iter()
+        .find(|(charset, _)| *charset == lower)
+        .map(|(name, encoding)| Charset { name, encoding })
+        .ok_or_else(|| anyhow!("Unsupported character set: '{}'", name))
+}
+
+/// Charset named by a `# charset: <name>` first line
+fn header_charset(bytes: &[u8]) -> Option<Result<Charset>> {
+    let first_line = bytes.split(|&b| b == b'\n').next()?;
+    let line = std::str::from_utf8(first_line).ok()?.trim();
+    let rest = line.strip_prefix('#')?.trim_start();
+    let (key, value) = rest.split_once(':')?;
+    if !key.trim().eq_ignore_ascii_case("charset") {
+        return None;
+    }
+    Some(parse_charset(value))
+}
+
+/// Read a `.test`/`.result` file. Its charset comes from the `# charset:`
+/// header, then `default`; without either the file must be valid UTF-8.
+pub fn read_text_file(path: &Path, default: Option<Charset>) -> Result<(String, Charset)> {
+    let bytes = std::fs::read(path)?;
+    let charset = match header_charset(&bytes) {
+        Some(charset) => Some(charset.with_context(|| format!("in {}", path.display()))?),
+        None => default,
+    };
+    match charset {
+        Some(charset) => Ok((charset.decode(&bytes).into_owned(), charset)),
+        None => {
+            let text = String::from_utf8(bytes).map_err(|_| {
+                anyhow!(
+                    "{} is not valid UTF-8; add a '# charset: <name>' first line or use --file-charset",
+                    path.display()
+                )
+            })?;
+            Ok((text, Charset::UTF8MB4))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_parse_charset() {
+        assert_eq!(parse_charset("GBK").unwrap().name(), "gbk");
+        assert_eq!(parse_charset("'latin1'").unwrap().name(), "latin1");
+        assert!(parse_charset("utf8mb3").unwrap().is_utf8());
+        assert!(parse_charset("ebcdic").is_err());
+    }
+
+    #[test]
+    fn test_decode_and_encode() {
+        let gbk = parse_charset("gbk").unwrap();
+        // "中文" in GBK
+        let bytes = [0xD6, 0xD0, 0xCE, 0xC4];
+        assert_eq!(gbk.decode(&bytes), "中文");
+        assert_eq!(gbk.encode("中文").as_ref(), &bytes);
+
+        let latin1 = parse_charset("latin1").unwrap();
+        assert_eq!(latin1.decode(&[0x63, 0x61, 0x66, 0xE9]), "café");
+        assert_eq!(Charset::default().decode("café".as_bytes()), "café");
+    }
+
+    #[test]
+    fn test_session_statement() {
+        assert_eq!(
+            parse_charset("gbk").unwrap().session_statement(),
+            "SET character_set_client = utf8mb4, character_set_connection = gbk, character_set_results = gbk"
+        );
+    }
+
+    #[test]
+    fn test_read_text_file() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"# charset: gbk\nSELECT '").unwrap();
+        file.write_all(&[0xD6, 0xD0]).unwrap();
+        file.write_all(b"';\n").unwrap();
+        let (text, charset) = read_text_file(file.path(), None).unwrap();
+        assert_eq!(text, "# charset: gbk\nSELECT '中';\n");
+        assert_eq!(charset.name(), "gbk");
+
+        let mut plain = tempfile::NamedTempFile::new().unwrap();
+        plain.write_all(&[b'a', 0xE9, b'\n']).unwrap();
+        assert!(read_text_file(plain.path(), None).is_err());
+        let latin1 = parse_charset("latin1").ok();
+        assert_eq!(read_text_file(plain.path(), latin1).unwrap().0, "aé\n");
+    }
+}
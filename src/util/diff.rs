@@ -0,0 +1,303 @@
+//! Line based unified diff used to report result mismatches.
+
+/// Above this many LCS cells (expected × actual lines of the differing middle
+/// part) the middle is reported as fully replaced instead
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// One changed region with its context lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// First expected line covered by the hunk (0-based) and number of lines
+    pub expected_start: usize,
+    pub expected_len: usize,
+    /// First actual line covered by the hunk (0-based) and number of lines
+    pub actual_start: usize,
+    pub actual_len: usize,
+    /// `@@` header followed by the context, removed and added lines
+    pub text: String,
+}
+
+/// Unified diff of two texts with `context` lines around each change. Returns
+/// an empty string when the texts have the same lines.
+pub fn unified_diff(
+    expected: &str,
+    actual: &str,
+    expected_name: &str,
+    actual_name: &str,
+    context: usize,
+) -> String {
+    let hunks = hunks(expected, actual, context);
+    if hunks.is_empty() {
+        return String::new();
+    }
+    let mut out = format!("--- {}\n+++ {}\n", expected_name, actual_name);
+    for hunk in &hunks {
+        out.push_str(&hunk.text);
+    }
+    out
+}
+
+/// Changed regions between two texts, with `context` lines around each change
+pub fn hunks(expected: &str, actual: &str, context: usize) -> Vec<Hunk> {
+    let a: Vec<&str> = expected.lines().collect();
+    let b: Vec<&str> = actual.lines().collect();
+    let ops = diff_ops(&a, &b, &|x, y| x == y);
+
+    // Position in both texts before each op
+    let mut positions = Vec::with_capacity(ops.len());
+    let (mut i, mut j) = (0, 0);
+    for op in &ops {
+        positions.push((i, j));
+        match op {
+            Op::Equal => {
+                i += 1;
+                j += 1;
+            }
+            Op::Delete => i += 1,
+            Op::Insert => j += 1,
+        }
+    }
+
+    let changes: Vec<usize> = (0..ops.len()).filter(|&k| ops[k] != Op::Equal).collect();
+    let mut hunks = Vec::new();
+    let mut index = 0;
+    while index < changes.len() {
+        // Changes closer than two contexts apart share a hunk
+        let first = changes[index];
+        let mut last = first;
+        while index + 1 < changes.len() && changes[index + 1] - last - 1 <= 2 * context {
+            index += 1;
+            last = changes[index];
+        }
+        let start = first.saturating_sub(context);
+        let end = (last + context + 1).min(ops.len());
+
+        let expected_len = ops[start..end].iter().filter(|op| **op != Op::Insert).count();
+        let actual_len = ops[start..end].iter().filter(|op| **op != Op::Delete).count();
+        let (expected_start, actual_start) = positions[start];
+        let mut text = format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(expected_start, expected_len),
+            hunk_range(actual_start, actual_len)
+        );
+        for k in start..end {
+            let (i, j) = positions[k];
+            match ops[k] {
+                Op::Equal => text.push_str(&format!(" {}\n", a[i])),
+                Op::Delete => text.push_str(&format!("-{}\n", a[i])),
+                Op::Insert => text.push_str(&format!("+{}\n", b[j])),
+            }
+        }
+        hunks.push(Hunk {
+            expected_start,
+            expected_len,
+            actual_start,
+            actual_len,
+            text,
+        });
+        index += 1;
+    }
+    hunks
+}
+
+/// The expected text with the accepted hunks replaced by the actual lines.
+/// `accepted[i]` tells whether `hunks[i]` is taken over.
+pub fn apply_hunks(expected: &str, actual: &str, hunks: &[Hunk], accepted: &[bool]) -> String {
+    let a: Vec<&str> = expected.lines().collect();
+    let b: Vec<&str> = actual.lines().collect();
+    let mut lines: Vec<&str> = Vec::with_capacity(a.len().max(b.len()));
+    let mut cursor = 0;
+    for (hunk, accept) in hunks.iter().zip(accepted) {
+        if !accept {
+            continue;
+        }
+        lines.extend_from_slice(&a[cursor..hunk.expected_start]);
+        lines.extend_from_slice(&b[hunk.actual_start..hunk.actual_start + hunk.actual_len]);
+        cursor = hunk.expected_start + hunk.expected_len;
+    }
+    lines.extend_from_slice(&a[cursor..]);
+
+    let mut merged = lines.join("\n");
+    if !merged.is_empty() {
+        merged.push('\n');
+    }
+    merged
+}
+
+/// `start,count` of a hunk header (GNU diff convention for empty ranges)
+fn hunk_range(position: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", position),
+        1 => format!("{}", position + 1),
+        _ => format!("{},{}", position + 1, count),
+    }
+}
+
+/// Pairs `(i, j)` of lines `a[i]` and `b[j]` that the edit script keeps as
+/// equal, where `eq` decides whether two lines are equal
+pub fn matching_lines(a: &[&str], b: &[&str], eq: &dyn Fn(&str, &str) -> bool) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for op in diff_ops(a, b, eq) {
+        match op {
+            Op::Equal => {
+                pairs.push((i, j));
+                i += 1;
+                j += 1;
+            }
+            Op::Delete => i += 1,
+            Op::Insert => j += 1,
+        }
+    }
+    pairs
+}
+
+fn diff_ops(a: &[&str], b: &[&str], eq: &dyn Fn(&str, &str) -> bool) -> Vec<Op> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| eq(x, y)).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| eq(x, y))
+        .count();
+
+    let mut ops = vec![Op::Equal; prefix];
+    ops.extend(middle_ops(
+        &a[prefix..a.len() - suffix],
+        &b[prefix..b.len() - suffix],
+        eq,
+    ));
+    ops.extend(std::iter::repeat_n(Op::Equal, suffix));
+    ops
+}
+
+/// Edit script of the differing middle part, from a longest common subsequence
+fn middle_ops(a: &[&str], b: &[&str], eq: &dyn Fn(&str, &str) -> bool) -> Vec<Op> {
+    let (n, m) = (a.len(), b.len());
+    let mut ops = Vec::with_capacity(n + m);
+    if n.saturating_mul(m) > MAX_LCS_CELLS {
+        ops.extend(std::iter::repeat_n(Op::Delete, n));
+        ops.extend(std::iter::repeat_n(Op::Insert, m));
+        return ops;
+    }
+
+    // lcs[i * (m + 1) + j]: length of the LCS of a[i..] and b[j..]
+    let width = m + 1;
+    let mut lcs = vec![0u32; (n + 1) * width];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * width + j] = if eq(a[i], b[j]) {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if eq(a[i], b[j]) {
+            ops.push(Op::Equal);
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            ops.push(Op::Delete);
+            i += 1;
+        } else {
+            ops.push(Op::Insert);
+            j += 1;
+        }
+    }
+    ops.extend(std::iter::repeat_n(Op::Delete, n - i));
+    ops.extend(std::iter::repeat_n(Op::Insert, m - j));
+    ops
+}
+
+/// Keep at most `max_lines` lines of `text` (0 keeps everything)
+pub fn truncate_lines(text: &str, max_lines: usize) -> String {
+    let total = text.lines().count();
+    if max_lines == 0 || total <= max_lines {
+        return text.to_string();
+    }
+    let mut kept: String = text
+        .lines()
+        .take(max_lines)
+        .flat_map(|line| [line, "\n"])
+        .collect();
+    kept.push_str(&format!("... ({} more diff lines)\n", total - max_lines));
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_texts() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb", "x", "y", 3), "");
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let expected = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let actual = "1\n2\n3\nfour\n5\n6\n7\n8\n9\n10\n11\n";
+        assert_eq!(
+            unified_diff(expected, actual, "r/t.result", "r/t.reject", 1),
+            "--- r/t.result\n+++ r/t.reject\n\
+             @@ -3,3 +3,3 @@\n 3\n-4\n+four\n 5\n\
+             @@ -10 +10,2 @@\n 10\n+11\n"
+        );
+    }
+
+    #[test]
+    fn test_nearby_changes_share_a_hunk() {
+        let diff = unified_diff("a\nb\nc\nd\n", "A\nb\nc\nD\n", "x", "y", 1);
+        assert_eq!(diff.matches("@@ ").count(), 1);
+        assert!(diff.contains("@@ -1,4 +1,4 @@\n-a\n+A\n b\n c\n-d\n+D\n"));
+    }
+
+    #[test]
+    fn test_apply_hunks() {
+        let expected = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+        let actual = "one\n2\n3\n4\n5\n6\n7\n8\nnine\nten\n";
+        let hunks = hunks(expected, actual, 1);
+        assert_eq!(hunks.len(), 2);
+
+        assert_eq!(apply_hunks(expected, actual, &hunks, &[true, true]), actual);
+        assert_eq!(apply_hunks(expected, actual, &hunks, &[false, false]), expected);
+        assert_eq!(
+            apply_hunks(expected, actual, &hunks, &[false, true]),
+            "1\n2\n3\n4\n5\n6\n7\n8\nnine\nten\n"
+        );
+        assert_eq!(
+            apply_hunks(expected, actual, &hunks, &[true, false]),
+            "one\n2\n3\n4\n5\n6\n7\n8\n9\n"
+        );
+        // A new result file is a single hunk against nothing
+        let new_file = super::hunks("", "a\nb\n", 3);
+        assert_eq!(new_file.len(), 1);
+        assert_eq!(apply_hunks("", "a\nb\n", &new_file, &[true]), "a\nb\n");
+    }
+
+    #[test]
+    fn test_matching_lines() {
+        let a = ["x", "1", "y"];
+        let b = ["x", "2", "3", "y"];
+        assert_eq!(matching_lines(&a, &b, &|x, y| x == y), vec![(0, 0), (2, 3)]);
+        let digits = |x: &str, y: &str| x == y || (x == "1" && y.parse::<u32>().is_ok());
+        assert_eq!(matching_lines(&a, &b, &digits), vec![(0, 0), (1, 1), (2, 3)]);
+    }
+
+    #[test]
+    fn test_truncate_lines() {
+        assert_eq!(truncate_lines("a\nb\nc\n", 0), "a\nb\nc\n");
+        assert_eq!(truncate_lines("a\nb\nc\n", 2), "a\nb\n... (1 more diff lines)\n");
+    }
+}
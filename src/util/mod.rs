@@ -1,3 +1,6 @@
+pub mod args;
+pub mod charset;
+pub mod diff;
 pub mod error_utils;
 pub mod regex;
 pub mod memory_pool;